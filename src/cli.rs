@@ -0,0 +1,586 @@
+//! Command line interface of the `w8_solids` binary
+//!
+//! Exits with 0 on success, 1 if a script or an image fails, and 2 if the arguments are wrong.
+
+use crate::{
+    demo,
+    graphics::{
+        img::ImageFormat,
+        parser::{animation::Animation, DWScript, Progress},
+        terminal::DisplayMode,
+        Canvas, PPMImg,
+    },
+    watch::Watcher,
+};
+use std::path::Path;
+
+pub const USAGE: &str = "\
+usage:
+    w8_solids render SCRIPT [options]   run a script
+    w8_solids check SCRIPT...           check scripts for errors without drawing
+    w8_solids demo [-o FILE] [--size WxH]
+                                        render the gallery animation
+    w8_solids convert IMAGE OUTPUT      convert a .ppm, .pgm, .pbm or .png IMAGE
+                                        to a .ppm, .png or .gif OUTPUT
+    w8_solids help                      show this message
+
+render options:
+    -o, --output FILE    save the image, or the GIF of an animation, to FILE
+                         (animations need a .gif FILE)
+                         (.svg draws shapes instead of pixels)
+    --format FORMAT      ppm, png or gif; the format of the image, or of animation frames
+                         (gif saves no frames, only the animation)
+    --size WxH           size of the image, the scene of the script is scaled to fit
+    --frames A-B         only render frames A to B of an animation, counting from 1
+    --anim-dir DIR       where animation frames are saved, anim by default
+    --export FILE        save the solids drawn as a 3d scene, in .obj, .ply or .stl
+                         (for animations, the last frame rendered)
+    --display MODE       how display commands show the image: window (with ImageMagick),
+                         ansi (colored characters) or sixel (sixel graphics)
+    --no-display         ignore display commands
+    --watch              render again whenever the script or its meshes change";
+
+pub const EXIT_SUCCESS: i32 = 0;
+/// A script had errors, or an image couldn't be saved
+pub const EXIT_FAILURE: i32 = 1;
+/// The arguments couldn't be understood
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct RenderArgs {
+    pub script: String,
+    pub output: Option<String>,
+    pub format: Option<ImageFormat>,
+    pub size: Option<(usize, usize)>,
+    /// First and last frame, counting from 1
+    pub frames: Option<(usize, usize)>,
+    pub anim_dir: Option<String>,
+    pub export: Option<String>,
+    pub display: Option<DisplayMode>,
+    pub no_display: bool,
+    /// Keep rendering whenever the script changes, until stopped
+    pub watch: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Render(RenderArgs),
+    Check(Vec<String>),
+    Demo {
+        output: String,
+        size: (usize, usize),
+    },
+    Convert {
+        input: String,
+        output: String,
+    },
+    Help,
+}
+
+/// Parse `WxH`, like `800x600`
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    let error = || format!("size must be WIDTHxHEIGHT, like 800x600, found `{}`", s);
+    let (width, height) = s.split_once(['x', 'X']).ok_or_else(error)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+/// Parse `A-B` or `A`, counting from 1
+fn parse_frames(s: &str) -> Result<(usize, usize), String> {
+    let error = || format!("frames must be FIRST-LAST, like 1-36, found `{}`", s);
+    let (first, last) = s.split_once('-').unwrap_or((s, s));
+    match (first.parse(), last.parse()) {
+        (Ok(first), Ok(last)) if first > 0 && first <= last => Ok((first, last)),
+        _ => Err(error()),
+    }
+}
+
+fn parse_display(s: &str) -> Result<DisplayMode, String> {
+    DisplayMode::from_name(s)
+        .ok_or_else(|| format!("display must be window, ansi or sixel, found `{}`", s))
+}
+
+fn parse_format(s: &str) -> Result<ImageFormat, String> {
+    ImageFormat::from_name(s)
+        .ok_or_else(|| format!("format must be ppm, png or gif, found `{}`", s))
+}
+
+/// Parse the arguments after the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Err(String::from("missing command")),
+    };
+
+    let mut positional = Vec::new();
+    let mut render = RenderArgs::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .map(String::as_str)
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => render.output = Some(value()?.to_string()),
+            "--format" => render.format = Some(parse_format(value()?)?),
+            "--size" => render.size = Some(parse_size(value()?)?),
+            "--frames" => render.frames = Some(parse_frames(value()?)?),
+            "--anim-dir" => render.anim_dir = Some(value()?.to_string()),
+            "--export" => render.export = Some(value()?.to_string()),
+            "--display" => render.display = Some(parse_display(value()?)?),
+            "--no-display" => render.no_display = true,
+            "--watch" => render.watch = true,
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option {}", flag))
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let only = |allowed: &[&str]| {
+        let given = [
+            ("--output", render.output.is_some()),
+            ("--format", render.format.is_some()),
+            ("--size", render.size.is_some()),
+            ("--frames", render.frames.is_some()),
+            ("--anim-dir", render.anim_dir.is_some()),
+            ("--export", render.export.is_some()),
+            ("--display", render.display.is_some()),
+            ("--no-display", render.no_display),
+            ("--watch", render.watch),
+        ];
+        match given
+            .iter()
+            .find(|(flag, given)| *given && !allowed.contains(flag))
+        {
+            Some((flag, _)) => Err(format!("{} can't be used with {}", flag, command)),
+            None => Ok(()),
+        }
+    };
+
+    match command {
+        "render" => match positional.as_slice() {
+            [script] => {
+                render.script = script.clone();
+                Ok(Command::Render(render))
+            }
+            [] => Err(String::from("render needs a script")),
+            _ => Err(String::from("render takes one script")),
+        },
+        "check" => {
+            only(&[])?;
+            if positional.is_empty() {
+                return Err(String::from("check needs at least one script"));
+            }
+            Ok(Command::Check(positional))
+        }
+        "demo" => {
+            only(&["--output", "--size"])?;
+            if let Some(arg) = positional.first() {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+            Ok(Command::Demo {
+                output: render
+                    .output
+                    .unwrap_or_else(|| demo::DEFAULT_PATH.to_string()),
+                size: render.size.unwrap_or((500, 500)),
+            })
+        }
+        "convert" => {
+            only(&[])?;
+            match positional.as_slice() {
+                [input, output] => Ok(Command::Convert {
+                    input: input.clone(),
+                    output: output.clone(),
+                }),
+                _ => Err(String::from("convert needs an image and an output file")),
+            }
+        }
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        command => Err(format!("unknown command `{}`", command)),
+    }
+}
+
+/// Returns the script set up as asked by `args`
+fn new_script(args: &RenderArgs) -> DWScript {
+    let mut script = DWScript::new(&args.script);
+    if let Some((width, height)) = args.size {
+        script.set_image_size(width, height);
+    }
+    if let Some((first, last)) = args.frames {
+        script.set_frame_range(first - 1, last - 1);
+    }
+    if let Some(dir) = &args.anim_dir {
+        script.set_anim_dir(dir);
+    }
+    if let Some(format) = args.format {
+        script.set_frame_format(Some(format).filter(|&f| f != ImageFormat::Gif));
+    }
+    if let Some(output) = &args.output {
+        if is_gif(output) {
+            script.set_gif_path(output);
+        }
+        if is_svg(output) {
+            script.draw_svg();
+        }
+    }
+    if let Some(path) = &args.export {
+        script.set_export_path(path);
+    }
+    if let Some(mode) = args.display {
+        script.set_display_mode(mode);
+    }
+    if args.no_display {
+        script.skip_displays();
+    }
+    script.on_progress(|progress| match progress {
+        Progress::Frame { frame, frames } => println!("Rendered frame {}/{}", frame, frames),
+        Progress::SavedGif(path) => println!("Saved {}", path.display()),
+        Progress::Exported { triangles, path } => {
+            println!("Exported {} triangles to {}", triangles, path)
+        }
+    });
+    script
+}
+
+/// Print the warnings of `animation` for `script`
+fn warn(script: &str, animation: &Animation) {
+    for warning in animation.warnings.iter() {
+        eprintln!("{}: warning: {}", script, warning);
+    }
+}
+
+/// Whether `path` is a GIF file, the only format animations are saved in
+fn is_gif(path: &str) -> bool {
+    ImageFormat::from_path(path) == Some(ImageFormat::Gif)
+}
+
+/// Returns an error if `-o` is given for an animated script but isn't a GIF file
+///
+/// Scripts with parse errors pass, so that rendering them reports the errors.
+fn check_output(args: &RenderArgs) -> Result<(), String> {
+    match &args.output {
+        Some(output) if !is_gif(output) => match DWScript::new(&args.script).check() {
+            Ok(Some(_)) => Err(animated_output_error(output)),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn animated_output_error(output: &str) -> String {
+    format!(
+        "{}: the script is animated, animations can only be saved as .gif",
+        output
+    )
+}
+
+/// Whether `path` is an SVG file, which scripts draw as shapes instead of pixels
+fn is_svg(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".svg")
+}
+
+/// Run the script, saving the image or animation as asked
+fn render(args: &RenderArgs, script: &mut DWScript) -> Result<(), String> {
+    let animation = script.do_parse().map_err(|e| e.to_string())?;
+    if let Some(animation) = &animation {
+        warn(&args.script, animation);
+        // caught by `check_output` unless the script became animated while watching
+        return match &args.output {
+            Some(output) if !is_gif(output) => Err(animated_output_error(output)),
+            _ => Ok(()),
+        };
+    }
+    if args.frames.is_some() {
+        return Err(format!(
+            "{}: --frames given, but the script isn't animated",
+            args.script
+        ));
+    }
+
+    // the image of a script that isn't animated
+    let output = match (&args.output, args.format) {
+        (Some(output), _) => output.clone(),
+        (None, Some(format)) => Path::new(&args.script)
+            .with_extension(format.extension())
+            .to_string_lossy()
+            .into_owned(),
+        (None, None) => return Ok(()),
+    };
+    match args.format {
+        Some(format) => script.image().save_as(&output, format),
+        None => script.save(&output),
+    }
+    .map_err(|e| format!("{}: couldn't save image: {}", output, e))?;
+    println!("Saved {}", output);
+    Ok(())
+}
+
+/// Load the image at `input` and save it to `output`, in the format given by its extension
+fn convert(input: &str, output: &str) -> Result<(), String> {
+    let img = PPMImg::load(input).map_err(|e| format!("{}: couldn't read image: {}", input, e))?;
+    img.save(output)
+        .map_err(|e| format!("{}: couldn't save image: {}", output, e))?;
+    println!("Saved {}", output);
+    Ok(())
+}
+
+/// Render the script every time it or one of its meshes changes, printing errors
+/// instead of stopping on them
+///
+/// Never returns, the process has to be stopped with Ctrl-C.
+fn watch(args: &RenderArgs) -> ! {
+    let mut watcher = Watcher::new();
+    loop {
+        let mut script = new_script(args);
+        if let Err(e) = render(args, &mut script) {
+            eprintln!("{}", e);
+        }
+        // files used by earlier versions of the script stay watched, in case they come back
+        for file in script.files() {
+            watcher.watch(&file);
+        }
+
+        let files: Vec<String> = watcher.files().map(|f| f.display().to_string()).collect();
+        println!(
+            "Watching {} for changes, press Ctrl-C to stop",
+            files.join(", ")
+        );
+        for file in watcher.wait() {
+            println!("{} changed", file.display());
+        }
+    }
+}
+
+/// Check every script, printing the errors of each
+///
+/// Returns whether all of them are fine.
+fn check(scripts: &[String]) -> bool {
+    let mut ok = true;
+    for filename in scripts {
+        match DWScript::new(filename).check() {
+            Ok(Some(animation)) => {
+                warn(filename, &animation);
+                println!("{}: ok, {} frames", filename, animation.frames)
+            }
+            Ok(None) => println!("{}: ok", filename),
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+/// Run the command given by `args`, the arguments after the program name,
+/// and return the exit code
+pub fn main(args: &[String]) -> i32 {
+    let command = match parse_args(args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let result = match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Render(args) if args.watch => watch(&args),
+        Command::Render(args) => {
+            if let Err(e) = check_output(&args) {
+                eprintln!("error: {}", e);
+                return EXIT_USAGE;
+            }
+            render(&args, &mut new_script(&args))
+        }
+        Command::Check(scripts) => {
+            return if check(&scripts) {
+                EXIT_SUCCESS
+            } else {
+                EXIT_FAILURE
+            }
+        }
+        Command::Demo { output, size } => demo::run(&output, size.0 as u32, size.1 as u32)
+            .map_err(|e| format!("{}: couldn't save animation: {}", output, e)),
+        Command::Convert { input, output } => convert(&input, &output),
+    };
+    match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn render_args() {
+        assert_eq!(
+            Ok(Command::Render(RenderArgs {
+                script: String::from("script.mdl"),
+                output: Some(String::from("out.png")),
+                format: Some(ImageFormat::Png),
+                size: Some((800, 600)),
+                frames: Some((1, 36)),
+                anim_dir: None,
+                export: Some(String::from("scene.obj")),
+                display: None,
+                no_display: true,
+                watch: false,
+            })),
+            parse(concat!(
+                "render script.mdl -o out.png --size 800x600 --frames 1-36 --format png ",
+                "--no-display --export scene.obj"
+            ))
+        );
+        assert_eq!(
+            Ok(Command::Render(RenderArgs {
+                script: String::from("script.mdl"),
+                frames: Some((5, 5)),
+                ..RenderArgs::default()
+            })),
+            parse("render --frames 5 script.mdl")
+        );
+        assert_eq!(
+            Ok(Command::Render(RenderArgs {
+                script: String::from("script.mdl"),
+                watch: true,
+                ..RenderArgs::default()
+            })),
+            parse("render script.mdl --watch")
+        );
+        assert_eq!(
+            Ok(Command::Render(RenderArgs {
+                script: String::from("script.mdl"),
+                display: Some(DisplayMode::Sixel),
+                ..RenderArgs::default()
+            })),
+            parse("render script.mdl --display sixel")
+        );
+    }
+
+    #[test]
+    fn bad_args() {
+        for args in [
+            "",
+            "draw script",
+            "render",
+            "render a b",
+            "render script --size 800",
+            "render script --size 0x600",
+            "render script --frames 3-1",
+            "render script --frames 0-2",
+            "render script --format jpg",
+            "render script -o",
+            "render script --export",
+            "check script --export a.obj",
+            "render script --fast",
+            "render script --display kitty",
+            "check",
+            "check script --size 10x10",
+            "demo script",
+            "demo --watch",
+            "convert a.png",
+            "convert a.png b.ppm c.gif",
+            "convert a.png b.ppm --size 10x10",
+        ] {
+            assert!(parse(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn animation_output() {
+        let dir = std::env::temp_dir().join("w8_solids_cli_output");
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("anim.mdl");
+        std::fs::write(&script, "frames 2\nbasename anim\nsphere 250 250 0 100").unwrap();
+        let run = |output: &str| {
+            let args: Vec<String> = [
+                "render",
+                &script.to_string_lossy(),
+                "-o",
+                &dir.join(output).to_string_lossy(),
+                "--anim-dir",
+                &dir.to_string_lossy(),
+                "--no-display",
+            ]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+            main(&args)
+        };
+
+        for output in ["out.png", "out.svg"].iter() {
+            assert_eq!(EXIT_USAGE, run(output), "{}", output);
+            assert!(!dir.join(output).exists(), "{}", output);
+        }
+        assert_eq!(EXIT_SUCCESS, run("out.gif"));
+        assert_eq!(b"GIF89a", &std::fs::read(dir.join("out.gif")).unwrap()[..6]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn convert_images() {
+        let dir = std::env::temp_dir().join("w8_solids_cli_convert");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let mut img = PPMImg::new(3, 2, 255);
+        img.plot(1, 2, 0.);
+        img.save(&path("in.ppm")).unwrap();
+
+        let run = |input: &str, output: &str| {
+            let args = [String::from("convert"), path(input), path(output)];
+            main(&args)
+        };
+        assert_eq!(EXIT_SUCCESS, run("in.ppm", "out.png"));
+        let converted = PPMImg::load(&path("out.png")).unwrap();
+        assert_eq!(img.pixels(), converted.pixels());
+        assert_eq!(EXIT_FAILURE, run("missing.ppm", "out.png"));
+        assert_eq!(EXIT_FAILURE, run("in.ppm", "out.jpg"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_commands() {
+        assert_eq!(
+            Ok(Command::Check(vec![
+                String::from("a.mdl"),
+                String::from("b.mdl")
+            ])),
+            parse("check a.mdl b.mdl")
+        );
+        assert_eq!(
+            Ok(Command::Demo {
+                output: String::from(demo::DEFAULT_PATH),
+                size: (500, 500)
+            }),
+            parse("demo")
+        );
+        assert_eq!(
+            Ok(Command::Convert {
+                input: String::from("a.png"),
+                output: String::from("a.ppm")
+            }),
+            parse("convert a.png a.ppm")
+        );
+        assert_eq!(Ok(Command::Help), parse("help"));
+        assert_eq!(Ok(Command::Help), parse("render script --help"));
+    }
+}
@@ -0,0 +1,140 @@
+//! The animation in the gallery, drawn with a `Drawer` instead of a script
+
+use crate::graphics::{
+    gif::GifEncoder, material::Material, matrix::transform as tr, Drawer, PPMImg, RGB,
+};
+use std::io;
+
+/// Where the animation is saved without `-o`; `product/img.gif` is the tracked gallery copy
+pub const DEFAULT_PATH: &str = "demo.gif";
+
+/// Render the animation as a `width` by `height` GIF at `path`
+pub fn run(path: &str, width: u32, height: u32) -> io::Result<()> {
+    let mut gif = GifEncoder::create(path, width, height)?;
+    gif.set_diff_transparency(true);
+
+    let mut drawer = Drawer::new(Box::new(PPMImg::new(height, width, 255)));
+    // the scene stays the same whatever the size of the image
+    drawer.set_scene_size(500., 500.);
+    let (scene_width, scene_height) = drawer.get_scene_size();
+
+    // colors!
+    // let default_fg = drawer.get_fg_color();
+    let light_yellow = RGB::new(245, 236, 66);
+    // let blue = RGB::new(66, 135, 245);
+    let magenta = RGB::new(239, 66, 245);
+    // let purple = RGB::new(209, 66, 245);
+    let brown = RGB::new(212, 143, 78);
+
+    drawer.add_material("light_yellow", Material::with_color(light_yellow).shiny());
+    drawer.add_material("magenta", Material::with_color(magenta));
+    drawer.add_material("brown", Material::with_color(brown).matte());
+
+    for rot in (0..360).step_by(10) {
+        // let mut stack: Vec<Matrix> = Vec::<Matrix>::new_stack();
+
+        // moving to the center
+        drawer.push_matrix();
+        {
+            drawer.transform_by(&tr::mv(scene_width / 2., scene_height / 2., 0.));
+            // drawer.transform_by(&tr::rotatex(rot as f64));
+
+            // drawing center sphere, rotate on rot
+            drawer.push_matrix();
+            {
+                drawer.transform_by(
+                    &(tr::rotatex(rot as f64) * tr::rotatey(rot as f64) * tr::rotatez(rot as f64)),
+                );
+                drawer.add_sphere((0., 0., 0.), 40., None)?;
+            }
+            drawer.pop_matrix()?;
+
+            // draw the torus around the sphere, rotate on rot
+            drawer.push_matrix();
+            {
+                drawer.transform_by(&(tr::rotatez(45.) * tr::rotatey(rot as f64)));
+                drawer.add_torus((0., 0., 0.), 10., 70., None)?;
+            }
+            drawer.pop_matrix()?;
+
+            // move away from center, draw first orbit
+            drawer.push_matrix();
+            {
+                // remember: transform_top needs to take the transformation in the opposite direction
+                drawer.transform_by(&tr::rotatez(rot as f64)); // <- var here
+                drawer.transform_by(&tr::mv(150., 0., 0.));
+
+                drawer.push_matrix();
+                {
+                    drawer.transform_by(&tr::rotatex(rot as f64));
+                    drawer.transform_by(&tr::rotatey(rot as f64));
+                    drawer.add_sphere((0., 0., 0.), 30., Some("magenta"))?;
+                }
+                drawer.pop_matrix()?;
+
+                // draw 1st satellite
+                drawer.push_matrix();
+                {
+                    drawer.transform_by(&tr::rotatex(rot as f64 * 3.)); // <- var here
+                    drawer.transform_by(&tr::mv(0., 80., 0.));
+                    drawer.add_sphere((0., 0., 0.), 20., Some("light_yellow"))?;
+
+                    // drawer.transform_by(&);
+                    drawer.transform_by(
+                        &(tr::rotatey(rot as f64 * 4.)
+                            * tr::rotatex(rot as f64 * 4.)
+                            * tr::rotatez(-45.)),
+                    );
+                    drawer.add_torus((0., 0., 0.), 5., 40., Some("brown"))?;
+                }
+                drawer.pop_matrix()?;
+
+                // 2nd satellite
+                drawer.push_matrix();
+                {
+                    drawer.transform_by(&tr::rotatex(rot as f64 * 3.));
+                    drawer.transform_by(&tr::mv(0., -80., 0.));
+                    drawer.add_sphere((0., 0., 0.), 20., Some("brown"))?;
+                }
+                drawer.pop_matrix()?;
+            }
+            drawer.pop_matrix()?;
+
+            drawer.push_matrix();
+            {
+                drawer.transform_by(&tr::rotatez(rot as f64)); // <- var here
+                drawer.transform_by(&tr::mv(-200., 0., 0.));
+
+                drawer.add_sphere((0., 0., 0.), 30., Some("brown"))?;
+
+                drawer.push_matrix();
+                {
+                    drawer.transform_by(&tr::rotatez(-rot as f64 * 3.));
+                    drawer.transform_by(&tr::mv(80., 0., 0.));
+
+                    drawer.add_sphere((0., 0., 0.), 20., Some("brown"))?;
+                }
+                drawer.pop_matrix()?;
+
+                drawer.push_matrix();
+                {
+                    drawer.transform_by(&tr::rotatez(-rot as f64 * 3.));
+                    drawer.transform_by(&tr::mv(-80., 0., 0.));
+
+                    drawer.add_sphere((0., 0., 0.), 20., Some("brown"))?;
+                }
+                drawer.pop_matrix()?;
+            }
+            drawer.pop_matrix()?;
+        }
+        drawer.pop_matrix()?;
+
+        gif.add_frame(&drawer.to_ppm())?;
+
+        drawer.clear();
+    }
+
+    gif.finish()?;
+    println!("Saved {}", path);
+    Ok(())
+}
@@ -0,0 +1,432 @@
+//! A camera looking into the scene with a perspective or orthographic projection
+//!
+//! Points go through the pipeline as rows, like everywhere else:
+//!
+//! ```text
+//! world --view--> camera --projection--> clip --divide by w--> ndc --> device
+//! ```
+//!
+//! In camera space the eye is at the origin looking down -z with +y up.
+//! Device coordinates are screen coordinates, with z larger closer to the eye,
+//! so the z-buffer and back-face culling work the same as without a camera.
+//! Lighting is done in camera space instead, since perspective skews normals in device space.
+
+use crate::graphics::{
+    matrix::{clip, projections},
+    vector::Vec3,
+    Matrix,
+};
+
+/// Vertical field of view of a new perspective camera, in degrees
+pub const DEFAULT_FOV: f64 = 60.;
+
+/// How a camera flattens the scene onto the canvas
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Further away is smaller
+    ///
+    /// `fov` is the vertical field of view in degrees, `aspect` is width / height of what the camera sees
+    /// and should match the canvas.
+    Perspective { fov: f64, aspect: f64 },
+    /// Everything keeps its size, the canvas shows the box from `left` to `right` and `bottom` to `top`
+    /// in camera space. Its aspect ratio should match the canvas.
+    Orthographic {
+        left: f64,
+        right: f64,
+        bottom: f64,
+        top: f64,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// Position of the camera
+    pub eye: Vec3,
+    /// Point the camera looks at
+    pub target: Vec3,
+    /// Which way is up, must not be parallel to the view direction
+    pub up: Vec3,
+    pub projection: Projection,
+    /// Distance from the eye to the near clipping plane, must be positive for perspective
+    pub near: f64,
+    /// Distance from the eye to the far clipping plane
+    pub far: f64,
+}
+
+impl Camera {
+    /// Perspective camera at `eye` looking at `target` with +y up, the default field of view and aspect ratio 1
+    pub fn new(eye: Vec3, target: Vec3) -> Self {
+        Camera {
+            eye,
+            target,
+            up: Vec3(0., 1., 0.),
+            projection: Projection::Perspective {
+                fov: DEFAULT_FOV,
+                aspect: 1.,
+            },
+            near: 1.,
+            far: 10000.,
+        }
+    }
+
+    /// Orthographic camera showing the box from (`left`, `bottom`, -`far`) to (`right`, `top`, -`near`)
+    /// in world coordinates, looking down -z
+    ///
+    /// Shapes in the box can then be drawn in world units, whatever the size of the canvas.
+    /// `near` can be negative to see things on the positive z side too.
+    pub fn orthographic(left: f64, right: f64, bottom: f64, top: f64, near: f64, far: f64) -> Self {
+        Camera {
+            eye: Vec3(0., 0., 0.),
+            target: Vec3(0., 0., -1.),
+            up: Vec3(0., 1., 0.),
+            projection: Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+            },
+            near,
+            far,
+        }
+    }
+
+    /// Orthographic camera fitting a `width` by `height` scene onto a `canvas_width` by `canvas_height` canvas
+    ///
+    /// The scene has its origin at the lower left, like the canvas. It is scaled to fill the canvas
+    /// and centered if the aspect ratios differ, so all of it is always visible.
+    pub fn fit_scene(width: f64, height: f64, canvas_width: f64, canvas_height: f64) -> Self {
+        let scale = (canvas_width / width).min(canvas_height / height);
+        let (view_width, view_height) = (canvas_width / scale, canvas_height / scale);
+        let (left, bottom) = ((width - view_width) / 2., (height - view_height) / 2.);
+        // deep enough for solids sticking far out of the screen
+        let depth = 10. * width.max(height);
+        Camera::orthographic(
+            left,
+            left + view_width,
+            bottom,
+            bottom + view_height,
+            -depth,
+            depth,
+        )
+    }
+
+    /// Matrix moving world coordinates into camera space
+    #[rustfmt::skip]
+    pub fn view_matrix(&self) -> Matrix {
+        // camera axes in world coordinates, z points from the target towards the eye
+        let z = (self.eye - self.target).norm();
+        let x = self.up.cross(z).norm();
+        let y = z.cross(x);
+        Matrix::new(4, 4, vec![
+            x.0,                y.0,                z.0,                0.,
+            x.1,                y.1,                z.1,                0.,
+            x.2,                y.2,                z.2,                0.,
+            -x.dot(self.eye),   -y.dot(self.eye),   -z.dot(self.eye),   1.,
+        ])
+    }
+
+    /// Matrix moving camera space into clip space
+    pub fn projection_matrix(&self) -> Matrix {
+        match self.projection {
+            Projection::Perspective { fov, aspect } => {
+                projections::perspective(fov.to_radians(), aspect, self.near, self.far)
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+            } => projections::orthographic(left, right, bottom, top, self.near, self.far),
+        }
+    }
+
+    /// Matrix moving world coordinates straight into clip space
+    pub fn view_projection(&self) -> Matrix {
+        self.view_matrix()._mul(&self.projection_matrix())
+    }
+
+    /// Returns `m` in world coordinates projected onto a `width` by `height` canvas
+    ///
+    /// `m` is made of primitives with `size` points each, 2 for edge matrices and 3 for polygon matrices.
+    /// They are clipped to the view volume first, so nothing behind the eye gets divided by w.
+    ///
+    /// z of the result comes from z in camera space instead of ndc z, which is squeezed into [-1, 1].
+    pub fn project(&self, m: &Matrix, size: usize, width: f64, height: f64) -> Matrix {
+        let m = m._mul(&self.view_projection());
+        let m = match size {
+            2 => m.clip_edges(&clip::FRUSTUM),
+            3 => m.clip_polygons(&clip::FRUSTUM),
+            _ => panic!(
+                "Can only project edges and triangles, not primitives of {} points",
+                size
+            ),
+        };
+        self.clip_to_device(m, width, height)
+    }
+
+    /// Returns polygon matrix `m` in world coordinates projected onto a `width` by `height` canvas,
+    /// along with the same polygons in camera space, row for row
+    ///
+    /// Polygons are lit in camera space, where they have their real shape.
+    pub fn project_polygons(&self, m: &Matrix, width: f64, height: f64) -> (Matrix, Matrix) {
+        let m = m._mul(&self.view_projection()).clip_polygons(&clip::FRUSTUM);
+        let mut camera_space = Matrix::new_polygon_matrix();
+        for point in m.iter_by_row() {
+            let p = self.clip_to_camera(point);
+            camera_space.append_row(&mut vec![p.0, p.1, p.2, 1.]);
+        }
+        (self.clip_to_device(m, width, height), camera_space)
+    }
+
+    /// Moves clipped matrix `m` from clip space onto a `width` by `height` canvas
+    fn clip_to_device(&self, mut m: Matrix, width: f64, height: f64) -> Matrix {
+        let depths: Vec<f64> = m.iter_by_row().map(|p| self.depth(p, width)).collect();
+        m.perspective_divide();
+        m.ndc_n1to1_to_device(width, height);
+        for (point, z) in m.mut_iter_by_row().zip(depths) {
+            point[2] = z;
+        }
+        m
+    }
+
+    /// Returns a point in clip space back in camera space
+    fn clip_to_camera(&self, point: &[f64]) -> Vec3 {
+        let (x, y, z, w) = (point[0], point[1], point[2], point[3]);
+        match self.projection {
+            // w in clip space is the distance in front of the eye
+            Projection::Perspective { fov, aspect } => {
+                let f = 1. / (fov.to_radians() / 2.).tan();
+                Vec3(x * aspect / f, y / f, -w)
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+            } => {
+                let (near, far) = (self.near, self.far);
+                Vec3(
+                    (x * (right - left) + right + left) / 2.,
+                    (y * (top - bottom) + top + bottom) / 2.,
+                    (z * (near - far) - (far + near)) / 2.,
+                )
+            }
+        }
+    }
+
+    /// Returns z on a `width` pixel wide canvas of a point in clip space
+    fn depth(&self, point: &[f64], width: f64) -> f64 {
+        let z = self.clip_to_camera(point).2;
+        match self.projection {
+            Projection::Perspective { .. } => z,
+            // scaled like x
+            Projection::Orthographic { left, right, .. } => z * width / (right - left),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_point(camera: &Camera, p: (f64, f64, f64)) -> (f64, f64, f64) {
+        let mut m = Matrix::new_edge_matrix();
+        m.append_edge(&[p.0, p.1, p.2, p.0, p.1, p.2]);
+        let m = camera.project(&m, 2, 500., 500.);
+        let row: Vec<f64> = m.row_iter(0).copied().collect();
+        (row[0], row[1], row[2])
+    }
+
+    fn close(a: (f64, f64, f64), b: (f64, f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6 && (a.2 - b.2).abs() < 1e-6
+    }
+
+    #[test]
+    fn view_matrix() {
+        let camera = Camera::new(Vec3(10., 0., 0.), Vec3(0., 0., 0.));
+        let p = Matrix::new(1, 4, vec![0., 0., 0., 1.])._mul(&camera.view_matrix());
+        let p: Vec<f64> = p.row_iter(0).copied().collect();
+        assert!(close((p[0], p[1], p[2]), (0., 0., -10.)));
+
+        // looking down -x, +z is to the left
+        let p = Matrix::new(1, 4, vec![0., 0., 5., 1.])._mul(&camera.view_matrix());
+        assert!(p.get(0, 0).unwrap() < 0.);
+    }
+
+    #[test]
+    fn projects_to_device() {
+        let mut camera = Camera::new(Vec3(0., 0., 100.), Vec3(0., 0., 0.));
+        camera.projection = Projection::Perspective {
+            fov: 90.,
+            aspect: 1.,
+        };
+
+        // the target lands in the middle, with the edges of the view on the edges of the canvas
+        let (x, y, _) = project_point(&camera, (0., 0., 0.));
+        assert!(close((x, y, 0.), (250., 250., 0.)));
+        let (x, y, _) = project_point(&camera, (50., -50., 0.));
+        assert!(close((x, y, 0.), (375., 125., 0.)));
+
+        // further away is smaller and deeper
+        let (x, _, z_far) = project_point(&camera, (100., 0., -100.));
+        assert!(close((x, 0., 0.), (375., 0., 0.)));
+        let (_, _, z_near) = project_point(&camera, (0., 0., 50.));
+        assert!(z_near > z_far);
+
+        // z is the depth in camera space
+        let (_, _, z) = project_point(&camera, (0., 0., 90.));
+        assert!((z + 10.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orthographic() {
+        let camera = Camera::orthographic(0., 100., 0., 100., -100., 100.);
+        assert!(close(
+            project_point(&camera, (50., 50., 0.)),
+            (250., 250., 0.)
+        ));
+        // z is scaled like x and y, so closer is still larger
+        assert!(close(
+            project_point(&camera, (10., 90., 10.)),
+            (50., 450., 50.)
+        ));
+        assert!(close(
+            project_point(&camera, (10., 90., -20.)),
+            (50., 450., -100.)
+        ));
+    }
+
+    #[test]
+    fn orthographic_is_resolution_independent() {
+        use crate::graphics::{golden::assert_golden, matrix::transform, Drawer, PPMImg, RGB};
+
+        let render = |size: u32| {
+            let mut drawer = Drawer::new(Box::new(PPMImg::new(size, size, 255)));
+            drawer.set_camera(Camera::orthographic(-2., 2., -2., 2., -10., 10.));
+            drawer.transform_by(&transform::rotatex(30.));
+            drawer.add_box((-1.5, 1.5, 0.), 1., 1., 1., None).unwrap();
+            drawer.add_sphere((0.5, 0.5, 0.), 1., None).unwrap();
+            drawer.add_torus((-0.5, -1., 0.), 0.2, 0.8, None).unwrap();
+            drawer.to_ppm()
+        };
+        let (small, large) = (render(250), render(500));
+        assert_golden("orthographic", &large);
+
+        // the solids cover the same part of both images
+        let covered = |img: &PPMImg| {
+            img.pixels().iter().filter(|&&p| p != RGB::gray(0)).count() as f64
+                / img.pixels().len() as f64
+        };
+        assert!((covered(&small) - covered(&large)).abs() < 0.005);
+
+        // and at the same spots
+        for &(x, y) in [(62, 55), (160, 95), (95, 175), (50, 180), (225, 225)].iter() {
+            assert_eq!(
+                small.pixels()[y * 250 + x] == RGB::gray(0),
+                large.pixels()[y * 2 * 500 + x * 2] == RGB::gray(0),
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn fit_scene() {
+        // same size, nothing moves
+        let camera = Camera::fit_scene(500., 500., 500., 500.);
+        assert!(close(
+            project_point(&camera, (100., 200., 30.)),
+            (100., 200., 30.)
+        ));
+
+        // wider canvas, the scene is centered with room on the sides
+        let camera = Camera::fit_scene(100., 100., 400., 200.);
+        let mut m = Matrix::new_edge_matrix();
+        m.append_edge(&[0., 0., 10., 100., 100., 10.]);
+        let m = camera.project(&m, 2, 400., 200.);
+        let points: Vec<Vec<f64>> = m.iter_by_row().map(|p| p[..3].to_vec()).collect();
+        assert_eq!(vec![vec![100., 0., 20.], vec![300., 200., 20.]], points);
+    }
+
+    #[test]
+    fn drawer_in_perspective() {
+        use crate::graphics::{golden::assert_golden, Drawer, PPMImg};
+
+        let mut drawer = Drawer::new(Box::new(PPMImg::new(500, 500, 255)));
+        let mut camera = Camera::new(Vec3(150., 200., 300.), Vec3(0., 0., 0.));
+        camera.projection = Projection::Perspective {
+            fov: DEFAULT_FOV,
+            aspect: drawer.aspect(),
+        };
+        drawer.set_camera(camera);
+        drawer.add_box((-150., 50., 50.), 100., 100., 100., None).unwrap();
+        drawer.add_sphere((80., 0., -50.), 70., None).unwrap();
+        drawer.add_torus((0., -40., 100.), 15., 60., None).unwrap();
+        assert_golden("perspective", &drawer.to_ppm());
+    }
+
+    #[test]
+    fn point_light_follows_orbit() {
+        use crate::graphics::{
+            light::{Light, LightConfig},
+            material::Material,
+            Drawer, PPMImg, RGB,
+        };
+
+        // a light off the +x face of a cube lights only that face, wherever the camera is
+        let unlit = LightConfig {
+            lights: vec![],
+            ..Default::default()
+        }
+        .get_color(Vec3(0., 0., 1.), Vec3(0., 0., 0.), &Material::default(), 255);
+        let render = |eye: Vec3| {
+            let camera = Camera::new(eye, Vec3(0., 0., 0.));
+            let mut drawer = Drawer::new(Box::new(PPMImg::new(500, 500, 255)));
+            drawer.set_camera(camera);
+            // replaces the default light
+            drawer.add_light(
+                "default",
+                Light::Point {
+                    pos: Vec3(1000., 0., 0.),
+                    color: RGB::gray(255),
+                },
+            );
+            drawer.add_box((-50., 50., 50.), 100., 100., 100., None).unwrap();
+            let img = drawer.to_ppm();
+            move |p: (f64, f64, f64)| {
+                let (x, y, _) = project_point(&camera, p);
+                img.pixels()[(499 - y as usize) * 500 + x as usize]
+            }
+        };
+
+        for &eye in [Vec3(400., 100., 400.), Vec3(400., 100., -400.)].iter() {
+            let color_at = render(eye);
+            let lit = color_at((50., 10., 10.));
+            assert!(lit.red > unlit.red + 50, "{:?} from {:?}", lit, eye);
+            // the face towards the camera and the top face
+            assert_eq!(unlit, color_at((10., 10., eye.2.signum() * 50.)), "from {:?}", eye);
+            assert_eq!(unlit, color_at((10., 50., 10.)), "from {:?}", eye);
+        }
+    }
+
+    #[test]
+    fn clips_behind_the_eye() {
+        let camera = Camera::new(Vec3(0., 0., 100.), Vec3(0., 0., 0.));
+        let mut m = Matrix::new_edge_matrix();
+        m.append_edge(&[0., 0., 0., 0., 0., 200.]);
+        m.append_edge(&[0., 0., 150., 0., 0., 200.]);
+        let m = camera.project(&m, 2, 500., 500.);
+        assert_eq!(2, m.iter_by_row().count());
+        // cut off at the near plane
+        assert!((m.get(1, 2).unwrap() + camera.near).abs() < 1e-6);
+
+        let mut m = Matrix::new_polygon_matrix();
+        m.append_polygon((-50., -50., 0.), (50., -50., 0.), (0., 0., 200.));
+        let m = camera.project(&m, 3, 500., 500.);
+        assert_eq!(0, m.iter_by_row().count() % 3);
+        for point in m.iter_by_row() {
+            assert!(point[2] <= -camera.near + 1e-6);
+        }
+    }
+}
@@ -1,10 +1,11 @@
-extern crate rand;
-use rand::Rng;
 use crate::graphics::{
-    matrix::Matrix,
-    utils::{mapper, polar_to_xy},
+    light::{LightConfig, ShadingMode},
+    material::Material,
+    matrix::{clip, Matrix},
+    render::RenderOptions,
+    utils::mapper,
     vector::Vec3,
-    RGB,
+    PPMImg, RGB,
 };
 use std::io;
 
@@ -19,9 +20,12 @@ pub trait Canvas {
     fn set_fg_color(&mut self, color: RGB);
     fn set_bg_color(&mut self, color: RGB);
     fn get_fg_color(&self) -> RGB;
-    fn get_bg_color(&self) -> RGB;
     fn width(&self) -> u32;
     fn height(&self) -> u32;
+    /// Largest value of a color channel
+    fn depth(&self) -> u16 {
+        255
+    }
     fn save(&self, filepath: &str) -> io::Result<()>;
     fn write_to_buf(&self, writer: &mut dyn io::Write) -> io::Result<()>;
 
@@ -31,6 +35,14 @@ pub trait Canvas {
     /// Clear the canvas of all drawings and resets configurations like z-buffer
     fn clear(&mut self);
 
+    /// Returns a copy of what has been drawn, e.g. to add it to an animation
+    fn to_ppm(&self) -> PPMImg;
+
+    /// Whether drawings going off one side come back on the other, in which case nothing is clipped
+    fn wraps(&self) -> bool {
+        false
+    }
+
     //----------------------------------------- default methods for drawing lines
 
     /// Draw a line from (x0, y0) to (x1, y1)
@@ -139,30 +151,22 @@ pub trait Canvas {
         }
     }
 
-    /// Draw a line from (x, y, z) with a certain magnitude and angle, on the same z-plane as the point
-    /// ## Note
-    /// Angle goes counter clockwise from x axis.
-    ///
-    /// Returns the other endpoint of the line (x1, y1, z) as a tuple
-    fn draw_line_degrees(
-        &mut self,
-        point: (f64, f64, f64),
-        angle_degrees: f64,
-        mag: f64,
-    ) -> (f64, f64, f64) {
-        let (dx, dy) = polar_to_xy(mag, angle_degrees);
-        let (x1, y1, z) = (point.0 + dx, point.1 + dy, point.2);
-
-        self.draw_line(point, (x1, y1, z));
-        return (x1, y1, z);
-    }
-
     //----------------------------------------- render edge matrix on screen
 
-    /// Draws an edge matrix
+    /// Draws an edge matrix, clipped to the canvas
     ///
     /// Number of edges must be a multiple of 2
     fn render_edge_matrix(&mut self, m: &Matrix) {
+        let clipped;
+        let m = if self.wraps() {
+            m
+        } else {
+            clipped = m.clip_edges(&clip::canvas_planes(
+                self.width() as f64,
+                self.height() as f64,
+            ));
+            &clipped
+        };
         let mut iter = m.iter_by_row();
         while let Some(point) = iter.next() {
             let p0 = (point[0], point[1], point[2]);
@@ -175,6 +179,7 @@ pub trait Canvas {
         }
     }
 
+    /// Draws an edge matrix in normalized device coordinates, with x and y in [-1, 1] covering the canvas
     fn render_ndc_edges_n1to1(&mut self, m: &Matrix) {
         let map_width = mapper(-1., 1., 0., self.width() as f64);
         let map_height = mapper(-1., 1., 0., self.height() as f64);
@@ -186,10 +191,10 @@ pub trait Canvas {
                 None => panic!("Number of edges must be a multiple of 2"),
             };
 
-            // we need to use inverse z to do depth buffer
+            // ndc z is -1 at the near plane, so flip it for the depth buffer
             self.draw_line(
-                (map_width(-x0), map_height(y0), 1. / z0),
-                (map_width(-x1), map_height(y1), 1. / z1),
+                (map_width(x0), map_height(y0), -z0),
+                (map_width(x1), map_height(y1), -z1),
             );
         }
     }
@@ -197,145 +202,197 @@ pub trait Canvas {
     /// Renders polygon matrix `m` onto screen.
     ///
     /// Removes hidden surface with back-face culling
-    /// Also draws scanlines
-    fn render_polygon_matrix(&mut self, m: &Matrix) {
-        // view vector for now: v = <0, 0, 1>, not needed for computation
+    /// Also draws scanlines, filling each triangle according to `options`.
+    /// Lit triangles are made of `material` and shaded with `light`.
+    fn render_polygon_matrix(
+        &mut self,
+        m: &Matrix,
+        light: &LightConfig,
+        material: &Material,
+        options: &RenderOptions,
+    ) {
+        self.render_projected_polygon_matrix(m, m, light, material, options)
+    }
+
+    /// Renders polygon matrix `m` onto screen, lit as the polygons in the same rows of `space`
+    ///
+    /// `m` is `space` projected onto the canvas, e.g. by a camera. Triangles are culled and filled
+    /// in `m`, while normals and points given to `light` come from `space`, where the lights are.
+    fn render_projected_polygon_matrix(
+        &mut self,
+        m: &Matrix,
+        space: &Matrix,
+        light: &LightConfig,
+        material: &Material,
+        options: &RenderOptions,
+    ) {
+        // culling assumes view vector: v = <0, 0, 1>
 
         // store default img color for ref later on
         let orig_color = self.get_fg_color();
 
-        let mut iter = m.iter_by_row();
-        while let Some(point) = iter.next() {
-            let p0 = (point[0], point[1], point[2]);
-            let p1 = match iter.next() {
-                Some(point1) => (point1[0], point1[1], point1[2]),
-                None => panic!("Number of points must be a multiple of 2 for edge matrix"),
-            };
-            let p2 = match iter.next() {
-                Some(point2) => (point2[0], point2[1], point2[2]),
-                None => panic!("Number of points must be a multiple of 3 for polygon matrix"),
-            };
+        let shading = options.shading;
+        let max = self.depth();
+        let mut triangle_colors = options.triangle_colors();
+        let vertex_normals = match shading {
+            ShadingMode::Gouraud | ShadingMode::Phong if triangle_colors.is_none() => {
+                space.vertex_normals()
+            }
+            _ => vec![],
+        };
+
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        let space_rows: Vec<&[f64]> = space.iter_by_row().collect();
+        let surface_normals = m.surface_normals();
+        let space_normals = space.surface_normals();
+        for (i, triangle) in rows.chunks(3).enumerate() {
+            if triangle.len() != 3 {
+                panic!("Number of points must be a multiple of 3 for polygon matrix");
+            }
+            let v = [
+                Vec3(triangle[0][0], triangle[0][1], triangle[0][2]),
+                Vec3(triangle[1][0], triangle[1][1], triangle[1][2]),
+                Vec3(triangle[2][0], triangle[2][1], triangle[2][2]),
+            ];
 
             // cull back face
-            let v0 = Vec3::from_pt(p0);
-            let v1 = Vec3::from_pt(p1);
-            let v2 = Vec3::from_pt(p2);
-
-            let surface_normal = (v1 - v0).cross(v2 - v0);
+            let surface_normal = surface_normals[i];
+            let fixed_color = triangle_colors
+                .as_mut()
+                .map(|colors| colors.color(i, surface_normal));
 
             if surface_normal.2 <= 0. {
                 continue;
             }
-            // self.set_fg_color(RGB::new(255, 255, 255));
-            // self.draw_line(p0, p1);
-            // self.draw_line(p1, p2);
-            // self.draw_line(p2, p0);
-
-            // draw scanlines
-            {
-                let mut rng = rand::thread_rng();
-                self.set_fg_color(RGB::new(
-                    rng.gen_range(0, 255),
-                    rng.gen_range(0, 255),
-                    rng.gen_range(0, 255),
-                ));
-
-                // sort points by y value
-                let mut points = [v0, v1, v2];
-                points.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-                let [vb, vm, vt] = points;
-
-                if (vb.y() - vm.y()).abs() == 0. {
-                    // special: two bottom vertices on same horizontal line
-                    let d0: Vec3 = (vt - vb) / (vt.y() - vb.y());
-                    let d1: Vec3 = (vt - vm) / (vt.y() - vm.y());
-
-                    let yoffsetb = vb.y().ceil() - vb.y();
-                    let yoffsetm = vm.y().ceil() - vm.y();
-
-                    let mut x0 = vb.x() + yoffsetb * d0.x();
-                    let mut x1 = vm.x() + yoffsetm * d1.x();
-
-                    let mut z0 = vb.z() + yoffsetb * d0.z();
-                    let mut z1 = vm.z() + yoffsetm * d1.z();
-
-                    for y in (vb.y().ceil() as i64)..(vt.y().ceil() as i64) {
-                        self.draw_scanline((x0, y as f64, z0), (x1, y as f64, z1));
-
-                        x0 += d0.x();
-                        z0 += d0.z();
-
-                        x1 += d1.x();
-                        z1 += d1.z();
-                    }
-                } else {
-                    // overall diff
-                    let dv: Vec3 = (vt - vb) / (vt.y() - vb.y());
-                    // bottom diff
-                    let dbottom: Vec3 = (vm - vb) / (vm.y() - vb.y());
-                    // top diff
-                    let dtop: Vec3 = (vt - vm) / (vt.y() - vm.y());
-
-                    let yoffsetb = vb.y().ceil() - vb.y();
-                    let yoffsetm = vm.y().ceil() - vm.y();
-
-                    let mut x0 = vb.x() + yoffsetb * dv.x();
-                    let mut x1 = vb.x() + yoffsetb * dbottom.x();
-                    let mut x2 = vm.x() + yoffsetm * dtop.x();
-
-                    let mut z0 = vb.z() + yoffsetb * dv.z();
-                    let mut z1 = vb.z() + yoffsetb * dbottom.z();
-                    let mut z2 = vm.z() + yoffsetm * dtop.z();
-
-                    for y in (vb.y().ceil() as i64)..(vm.y().ceil() as i64) {
-                        self.draw_scanline((x0, y as f64, z0), (x1, y as f64, z1));
-
-                        x0 += dv.x();
-                        x1 += dbottom.x();
-
-                        z0 += dv.z();
-                        z1 += dbottom.z();
-                    }
-                    for y in (vm.y().ceil() as i64)..(vt.y().ceil() as i64) {
-                        self.draw_scanline((x0, y as f64, z0), (x2, y as f64, z2));
-
-                        x0 += dv.x();
-                        x2 += dtop.x();
-                        z0 += dv.z();
-                        z2 += dtop.z();
-                    }
+
+            if let Some(color) = fixed_color {
+                self.fill_triangle(v, [Vec3(0., 0., 0.); 3], &|_, _| color);
+                continue;
+            }
+
+            let s = &space_rows[i * 3..i * 3 + 3];
+            let sv = [
+                Vec3(s[0][0], s[0][1], s[0][2]),
+                Vec3(s[1][0], s[1][1], s[1][2]),
+                Vec3(s[2][0], s[2][1], s[2][2]),
+            ];
+            match shading {
+                ShadingMode::Flat => {
+                    let center = (sv[0] + sv[1] + sv[2]) / 3.;
+                    let color = light.get_color(space_normals[i], center, material, max);
+                    self.fill_triangle(v, [Vec3(0., 0., 0.); 3], &|_, _| color);
+                }
+                ShadingMode::Gouraud => {
+                    // interpolate colors computed at each vertex
+                    let n = &vertex_normals[i * 3..i * 3 + 3];
+                    let colors = [
+                        light.get_color(n[0], sv[0], material, max).into(),
+                        light.get_color(n[1], sv[1], material, max).into(),
+                        light.get_color(n[2], sv[2], material, max).into(),
+                    ];
+                    self.fill_triangle(v, colors, &|_, color| color.into());
+                }
+                ShadingMode::Phong => {
+                    // interpolate normals and light every pixel, at the same spot in `space`
+                    let n = &vertex_normals[i * 3..i * 3 + 3];
+                    self.fill_triangle(v, [n[0], n[1], n[2]], &|point, normal| {
+                        let (a, b, c) = barycentric(v, point);
+                        light.get_color(normal, sv[0] * a + sv[1] * b + sv[2] * c, material, max)
+                    });
                 }
             }
         }
         self.set_fg_color(orig_color);
     }
 
-    fn draw_scanline(&mut self, p0: (f64, f64, f64), p1: (f64, f64, f64)) {
-        assert_eq!(p0.1, p1.1, "Scanline should be horizontal");
+    /// Fill the triangle with vertices `v` using scanlines
+    ///
+    /// `attrs` are values attached to each vertex that are interpolated across the triangle.
+    /// Each pixel is colored with `shade(point, attr)`.
+    fn fill_triangle(&mut self, v: [Vec3; 3], attrs: [Vec3; 3], shade: &dyn Fn(Vec3, Vec3) -> RGB) {
+        // sort points by y value
+        let mut points = [(v[0], attrs[0]), (v[1], attrs[1]), (v[2], attrs[2])];
+        points.sort_by(|a, b| (a.0).1.partial_cmp(&(b.0).1).unwrap());
+        let [(vb, ab), (vm, am), (vt, at)] = points;
+
+        // change in point and attr for each step in y, zero for horizontal edges since they are never stepped along
+        let delta = |(p0, a0): (Vec3, Vec3), (p1, a1): (Vec3, Vec3)| {
+            let dy = p1.y() - p0.y();
+            if dy > 0. {
+                ((p1 - p0) / dy, (a1 - a0) / dy)
+            } else {
+                (Vec3(0., 0., 0.), Vec3(0., 0., 0.))
+            }
+        };
+        // overall diff
+        let (dv, da) = delta((vb, ab), (vt, at));
+        // bottom diff
+        let (dvbottom, dabottom) = delta((vb, ab), (vm, am));
+        // top diff
+        let (dvtop, datop) = delta((vm, am), (vt, at));
+
+        let yoffsetb = vb.y().ceil() - vb.y();
+        let yoffsetm = vm.y().ceil() - vm.y();
+
+        let (mut v0, mut a0) = (vb + dv * yoffsetb, ab + da * yoffsetb);
+        let (mut v1, mut a1) = (vb + dvbottom * yoffsetb, ab + dabottom * yoffsetb);
+        let (mut v2, mut a2) = (vm + dvtop * yoffsetm, am + datop * yoffsetm);
+
+        for y in (vb.y().ceil() as i64)..(vm.y().ceil() as i64) {
+            self.draw_shaded_scanline(y, (v0, a0), (v1, a1), shade);
+
+            v0 = v0 + dv;
+            a0 = a0 + da;
+            v1 = v1 + dvbottom;
+            a1 = a1 + dabottom;
+        }
+        for y in (vm.y().ceil() as i64)..(vt.y().ceil() as i64) {
+            self.draw_shaded_scanline(y, (v0, a0), (v2, a2), shade);
+
+            v0 = v0 + dv;
+            a0 = a0 + da;
+            v2 = v2 + dvtop;
+            a2 = a2 + datop;
+        }
+    }
 
+    /// Draw a horizontal line at `y` between two (point, attr) pairs, interpolating z and attr
+    ///
+    /// Each pixel is colored with `shade(point, attr)`.
+    fn draw_shaded_scanline(
+        &mut self,
+        y: i64,
+        p0: (Vec3, Vec3),
+        p1: (Vec3, Vec3),
+        shade: &dyn Fn(Vec3, Vec3) -> RGB,
+    ) {
         // swap variables if needed, since we are always going from left to right
-        let (p0, p1) = if p0.0 > p1.0 { (p1, p0) } else { (p0, p1) };
+        let ((v0, a0), (v1, a1)) = if p0.0.x() > p1.0.x() { (p1, p0) } else { (p0, p1) };
 
-        let (x0, y0, z0, x1, z1) = (
-            p0.0,
-            p0.1,
-            p0.2,
-            p1.0,
-            p1.2,
-        );
+        let dx = v1.x() - v0.x();
+        if dx <= 0. {
+            return;
+        }
+        let (dv, da) = ((v1 - v0) / dx, (a1 - a0) / dx);
 
-        let y0 = y0.ceil() as i64;
-        let xoffset = x0.ceil() - x0;
-        // calculate  values and then truncate
-        // println!("x1 - x0: {} - {}", x1, x0);
+        // skip the pixels off the canvas
+        let (mut xstart, mut xend) = (v0.x().ceil() as i64, v1.x().ceil() as i64);
+        if !self.wraps() {
+            if y < 0 || y >= self.height() as i64 {
+                return;
+            }
+            xstart = xstart.max(0);
+            xend = xend.min(self.width() as i64);
+        }
 
-        let z_inc = (z1 - z0) / (x1 - x0) as f64;
-        
-        let mut z = z0 + xoffset * z_inc;
+        let xoffset = xstart as f64 - v0.x();
+        let (mut v, mut a) = (v0 + dv * xoffset, a0 + da * xoffset);
 
-        for x in (x0.ceil() as i64)..(x1.ceil() as i64) {
-            self.plot(x as i32, y0 as i32, z);
-            z += z_inc;
+        for x in xstart..xend {
+            self.set_fg_color(shade(Vec3(v.x(), y as f64, v.z()), a));
+            self.plot(x as i32, y as i32, v.z());
+            v = v + dv;
+            a = a + da;
         }
     }
     // fn render_polygon_with_stack(&mut self, stack: &impl MStack<Matrix>, m: &Matrix) {
@@ -347,11 +404,22 @@ pub trait Canvas {
     // }
 }
 
+/// Returns the weights of the vertices of triangle `v` that add up to `p`, ignoring z
+fn barycentric(v: [Vec3; 3], p: Vec3) -> (f64, f64, f64) {
+    let area = (v[1] - v[0]).cross(v[2] - v[0]).2;
+    if area == 0. {
+        return (1., 0., 0.);
+    }
+    let a = (v[1] - p).cross(v[2] - p).2 / area;
+    let b = (v[2] - p).cross(v[0] - p).2 / area;
+    (a, b, 1. - a - b)
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::PPMImg;
     use super::*;
-    use crate::graphics::utils::display_ppm;
+    use crate::graphics::golden::assert_golden;
 
     #[test]
     fn test_render_polygon_triangle() {
@@ -366,12 +434,17 @@ mod tests {
         let mut m = Matrix::new_polygon_matrix();
         m.append_polygon(p0, p1, p2);
 
-        img_polygon.render_polygon_matrix(&m);
+        img_polygon.render_polygon_matrix(
+            &m,
+            &LightConfig::default(),
+            &Material::default(),
+            &RenderOptions::default(),
+        );
         img_ln.draw_line(p0, p1);
         img_ln.draw_line(p1, p2);
         img_ln.draw_line(p2, p0);
 
-        display_ppm(&img_polygon);
+        assert_golden("polygon_triangle", &img_polygon);
         assert_ne!(
             img_ln, img_polygon,
             "Expect equivalent images by adding lines vs. drawing polygon"
@@ -384,8 +457,13 @@ mod tests {
         m.append_polygon((150., 400., 0.), (100., 100., 0.), (300., 100., 0.));
         m.append_polygon((250., 400., 10.), (50., 50., -10.), (400., 400., 10.));
         let mut img = PPMImg::new(500, 500, 255);
-        img.render_polygon_matrix(&m);
-        img.save("spiky.png").expect("Error writing to file");
+        img.render_polygon_matrix(
+            &m,
+            &LightConfig::default(),
+            &Material::default(),
+            &RenderOptions::default(),
+        );
+        assert_golden("scanline_special", &img);
     }
 
     #[test]
@@ -411,7 +489,37 @@ mod tests {
         );
 
         let mut img = PPMImg::new(500, 500, 255);
-        img.render_polygon_matrix(&m);
-        img.save("temp.png").expect("Error writing to file");
+        img.render_polygon_matrix(
+            &m,
+            &LightConfig::default(),
+            &Material::default(),
+            &RenderOptions::default(),
+        );
+        assert_golden("scanline_regression_streaks", &img);
+    }
+
+    #[test]
+    fn test_seeded_fill_is_reproducible() {
+        use crate::graphics::render::FillMode;
+
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_sphere((250., 250., 0.), 150.);
+        let render = |fill: FillMode| {
+            let mut img = PPMImg::new(500, 500, 255);
+            img.render_polygon_matrix(
+                &m,
+                &LightConfig::default(),
+                &Material::default(),
+                &RenderOptions {
+                    fill,
+                    ..Default::default()
+                },
+            );
+            img
+        };
+
+        let img = render(FillMode::Random { seed: 42 });
+        assert!(img == render(FillMode::Random { seed: 42 }));
+        assert!(img != render(FillMode::Random { seed: 43 }));
     }
 }
@@ -1,9 +1,11 @@
 #![allow(dead_code)]
+#![allow(clippy::upper_case_acronyms)]
 
+use crate::graphics::vector::Vec3;
 use std::cmp;
 use std::convert;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct RGB {
     pub red: u16,
     pub blue: u16,
@@ -67,6 +69,21 @@ impl convert::From<HSL> for RGB {
     }
 }
 
+/// Treats (red, green, blue) as a vector, useful for interpolating colors
+impl convert::From<RGB> for Vec3 {
+    fn from(color: RGB) -> Vec3 {
+        Vec3(color.red as f64, color.green as f64, color.blue as f64)
+    }
+}
+
+/// Rounds each component of the vector, negative components become 0
+impl convert::From<Vec3> for RGB {
+    fn from(v: Vec3) -> RGB {
+        let channel = |c: f64| c.max(0.).round() as u16;
+        RGB::new(channel(v.0), channel(v.1), channel(v.2))
+    }
+}
+
 fn fmax2(a: f64, b: f64, prec: i32) -> f64 {
     let fprec = prec as f64;
     cmp::max((a * fprec).round() as i32, (b * fprec).round() as i32) as f64 / fprec
@@ -1,21 +1,154 @@
-use crate::graphics::{Canvas, Matrix, RGB};
-use std::io;
+use crate::graphics::{
+    camera::Camera,
+    light::{Light, LightConfig, ShadingMode},
+    material::Material,
+    matrix::dim3::Polyhedron,
+    recording::Recording,
+    render::{FillMode, RenderOptions},
+    terminal::{self, DisplayMode},
+    vector::Vec3,
+    Canvas, Matrix, PPMImg, RGB,
+};
+use std::{collections::HashMap, error::Error, fmt, io};
+
+/// Names a `Drawer` was asked to use without them being defined
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DrawError {
+    /// No material was added with `add_material` under this name
+    UnknownMaterial(String),
+    /// No coordinate system was saved with `save_coord_system` under this name
+    UnknownCoordSystem(String),
+    /// `pop_matrix` without a matching `push_matrix`
+    EmptyStack,
+}
+
+impl fmt::Display for DrawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawError::UnknownMaterial(name) => write!(f, "unknown material \"{}\"", name),
+            DrawError::UnknownCoordSystem(name) => {
+                write!(f, "unknown coordinate system \"{}\"", name)
+            }
+            DrawError::EmptyStack => write!(f, "pop without matching push"),
+        }
+    }
+}
+
+impl Error for DrawError {}
+
+impl From<DrawError> for io::Error {
+    fn from(e: DrawError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
 
 /// A procedural interface to simplfy drawing
 pub struct Drawer {
     stack: Vec<Matrix>,
     canvas: Box<dyn Canvas>,
+    light_config: LightConfig,
+    /// Material used by solids that don't name one
+    material: Material,
+    materials: HashMap<String, Material>,
+    render_options: RenderOptions,
+    coord_systems: HashMap<String, Matrix>,
+    /// Projects shapes onto the canvas, or `None` to draw in scene coordinates
+    camera: Option<Camera>,
+    /// Size of the scene, in the units shapes are drawn in without a camera
+    scene_size: (f64, f64),
+    /// Fits the scene onto the canvas, `None` if they are the same size
+    scene_camera: Option<Camera>,
+    display_mode: DisplayMode,
+    /// Solids drawn since `start_recording`, if recording
+    recording: Option<Recording>,
 }
 
 // helpers
 impl Drawer {
     pub fn render_edges_with_stack(&mut self, m: &Matrix) {
-        self.canvas.render_edge_matrix(&(m * self.get_top_matrix()))
+        self.render_edges(&(m * self.get_top_matrix()))
     }
 
-    pub fn render_polygons_with_stack(&mut self, m: &Matrix) {
-        self.canvas
-            .render_polygon_matrix(&(m * self.get_top_matrix()))
+    /// Render edge matrix `m` that is already transformed by the stack
+    fn render_edges(&mut self, m: &Matrix) {
+        let m = self.project(m, 2);
+        self.canvas.render_edge_matrix(&m)
+    }
+
+    /// Returns `m` projected by the camera onto the canvas, or `m` if there is no camera
+    ///
+    /// `size` is the number of points in each primitive, 2 for edges and 3 for polygons
+    fn project(&self, m: &Matrix, size: usize) -> Matrix {
+        match self.camera.as_ref().or(self.scene_camera.as_ref()) {
+            Some(camera) => camera.project(
+                m,
+                size,
+                self.canvas.width() as f64,
+                self.canvas.height() as f64,
+            ),
+            None => m.clone(),
+        }
+    }
+
+    /// Render polygon matrix `m` made of the material named `material`, or the default material if `None`
+    ///
+    /// The polygons are also kept if recording, see `start_recording`.
+    pub fn render_polygons_with_stack(
+        &mut self,
+        m: &Matrix,
+        material_name: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let material = match material_name {
+            Some(name) => self
+                .materials
+                .get(name)
+                .ok_or_else(|| DrawError::UnknownMaterial(name.to_string()))?,
+            None => &self.material,
+        };
+        let m = m * self.get_top_matrix();
+        if let Some(recording) = self.recording.as_mut() {
+            recording.record(&m, material_name, material, &self.render_options);
+        }
+        match self.camera.as_ref().or(self.scene_camera.as_ref()) {
+            Some(camera) => {
+                let (width, height) = (self.canvas.width() as f64, self.canvas.height() as f64);
+                let (projected, camera_space) = camera.project_polygons(&m, width, height);
+                let light = self.camera_light_config(camera);
+                self.canvas.render_projected_polygon_matrix(
+                    &projected,
+                    &camera_space,
+                    &light,
+                    material,
+                    &self.render_options,
+                );
+            }
+            None => self.canvas.render_polygon_matrix(
+                &m,
+                &self.light_config,
+                material,
+                &self.render_options,
+            ),
+        }
+        Ok(())
+    }
+
+    /// The lighting in the camera space of `camera`, where polygons are lit when there is one
+    fn camera_light_config(&self, camera: &Camera) -> LightConfig {
+        let view = camera.view_matrix();
+        LightConfig {
+            // the viewer is the camera, unless it only fits the scene onto the canvas
+            view: match self.camera {
+                Some(_) => Vec3(0., 0., 1.),
+                None => self.light_config.view,
+            },
+            ambient: self.light_config.ambient,
+            lights: self
+                .light_config
+                .lights
+                .iter()
+                .map(|(name, light)| (name.clone(), light.transformed(&view)))
+                .collect(),
+        }
     }
 
     fn get_top_matrix(&self) -> &Matrix {
@@ -29,27 +162,22 @@ impl Drawer {
     }
 }
 
-// colors
-impl Drawer {
-    pub fn set_fg_color(&mut self, color: RGB) {
-        self.canvas.set_fg_color(color);
-    }
-    pub fn set_bg_color(&mut self, color: RGB) {
-        self.canvas.set_bg_color(color);
-    }
-    pub fn get_fg_color(&self) -> RGB {
-        self.canvas.get_fg_color()
-    }
-    pub fn get_bg_color(&self) -> RGB {
-        self.canvas.get_bg_color()
-    }
-}
-
 impl Drawer {
     pub fn new(canvas: Box<dyn Canvas>) -> Self {
+        let scene_size = (canvas.width() as f64, canvas.height() as f64);
         Drawer {
             stack: Drawer::new_stack(),
             canvas,
+            light_config: LightConfig::default(),
+            material: Material::default(),
+            materials: HashMap::new(),
+            render_options: RenderOptions::default(),
+            coord_systems: HashMap::new(),
+            camera: None,
+            scene_size,
+            scene_camera: None,
+            display_mode: DisplayMode::default(),
+            recording: None,
         }
     }
 
@@ -68,21 +196,148 @@ impl Drawer {
         self.canvas.save(filepath)
     }
 
+    /// Show the canvas as set by `set_display_mode`, in a window by default
     pub fn display(&self) {
-        self.canvas.display();
+        match self.display_mode {
+            DisplayMode::Window => self.canvas.display(),
+            mode => terminal::display(&self.canvas.to_ppm(), mode),
+        }
     }
-    
-    pub fn write_to_buf(&self, writer: &mut dyn io::Write) -> io::Result<()> {
-        self.canvas.write_to_buf(writer)
+
+    /// Set how `display` shows the canvas, e.g. in the terminal when there is no window system
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        self.display_mode = mode;
+    }
+
+    /// Returns a copy of the canvas, e.g. to add it to an animation
+    pub fn to_ppm(&self) -> PPMImg {
+        self.canvas.to_ppm()
+    }
+}
+
+// camera
+impl Drawer {
+    /// Draw everything after this as seen by `camera`
+    ///
+    /// Shapes are then in world coordinates, which the camera projects onto the canvas.
+    /// With an orthographic camera, scenes can be drawn in world units whatever the size of the canvas.
+    /// Lights stay in world coordinates, and the camera is the viewer for specular highlights.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = Some(camera);
+    }
+
+    /// Go back to drawing in scene coordinates, returning the camera if there was one
+    pub fn remove_camera(&mut self) -> Option<Camera> {
+        self.camera.take()
+    }
+
+    /// Width / height of the canvas, the aspect ratio a camera should use
+    pub fn aspect(&self) -> f64 {
+        self.canvas.width() as f64 / self.canvas.height() as f64
+    }
+
+    /// Draw without a camera in a `width` by `height` scene instead of in pixels
+    ///
+    /// The scene has its origin at the lower left, and is scaled to fit the canvas,
+    /// centered if the aspect ratios differ. The scene is the size of the canvas by default.
+    /// Lights are in scene coordinates too, so they scale along with the solids.
+    pub fn set_scene_size(&mut self, width: f64, height: f64) {
+        let (canvas_width, canvas_height) =
+            (self.canvas.width() as f64, self.canvas.height() as f64);
+        self.scene_size = (width, height);
+        self.scene_camera = if (width, height) == (canvas_width, canvas_height) {
+            None
+        } else {
+            Some(Camera::fit_scene(width, height, canvas_width, canvas_height))
+        };
+    }
+
+    pub fn get_scene_size(&self) -> (f64, f64) {
+        self.scene_size
+    }
+}
+
+// lighting
+impl Drawer {
+    /// Set the color of the global ambient light
+    pub fn set_ambient(&mut self, color: RGB) {
+        self.light_config.ambient = color;
+    }
+
+    /// Add a light source named `name`, replacing any light with the same name
+    ///
+    /// The light is transformed by the current top of the coordinate stack
+    pub fn add_light(&mut self, name: &str, light: Light) {
+        let light = light.transformed(self.get_top_matrix());
+        self.light_config.add_light(name, light);
+    }
+
+    /// Set how polygons are shaded
+    pub fn set_shading(&mut self, shading: ShadingMode) {
+        self.render_options.shading = shading;
+    }
+
+    /// Set where the colors of polygons come from
+    pub fn set_fill_mode(&mut self, fill: FillMode) {
+        self.render_options.fill = fill;
+    }
+}
+
+// recording
+impl Drawer {
+    /// Keep every solid drawn from now on, as moved by the coordinate stack, see `Recording`
+    ///
+    /// Drops what was recorded so far. Clearing the canvas doesn't clear the recording.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
+    }
+
+    /// Stop recording and return what was recorded, or `None` if not recording
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+}
+
+// materials
+impl Drawer {
+    /// Register `material` as `name`, replacing any material with the same name
+    pub fn add_material(&mut self, name: &str, material: Material) {
+        self.materials.insert(name.to_string(), material);
     }
 }
 
 // one dimensional stuff
 impl Drawer {
-    pub fn draw_line(&mut self, p0: (f64, f64, f64), p1: (f64, f64, f64)) {
+    /// Draw a line from `p0` to `p1`, with each endpoint in its own coordinate system.
+    ///
+    /// `coord0` and `coord1` name coordinate systems saved with `save_coord_system`, `None` means the top of the stack.
+    pub fn draw_line_across(
+        &mut self,
+        p0: (f64, f64, f64),
+        coord0: Option<&str>,
+        p1: (f64, f64, f64),
+        coord1: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let transform = |d: &Drawer, (x, y, z): (f64, f64, f64), coord: Option<&str>| {
+            let m = match coord {
+                Some(name) => d
+                    .get_coord_system(name)
+                    .ok_or_else(|| DrawError::UnknownCoordSystem(name.to_string()))?,
+                None => d.get_top_matrix(),
+            };
+            let p = Matrix::new(1, 4, vec![x, y, z, 1.])._mul(m);
+            Ok((
+                p.get(0, 0).unwrap(),
+                p.get(0, 1).unwrap(),
+                p.get(0, 2).unwrap(),
+            ))
+        };
+        let (p0, p1) = (transform(self, p0, coord0)?, transform(self, p1, coord1)?);
+
         let mut edges = Matrix::new_edge_matrix();
         edges.append_edge(&[p0.0, p0.1, p0.2, p1.0, p1.1, p1.2]);
-        self.render_edges_with_stack(&edges);
+        self.render_edges(&edges);
+        Ok(())
     }
     pub fn draw_circle(&mut self, c: (f64, f64, f64), r: f64) {
         let mut edges = Matrix::new_edge_matrix();
@@ -113,21 +368,122 @@ impl Drawer {
 }
 
 // 2d shapes
+//
+// `material` names a material registered with `add_material`; `None` uses the default material.
+// Nothing is drawn if there is no material named `material`.
 impl Drawer {
-    pub fn add_box(&mut self, (x, y, z): (f64, f64, f64), dx: f64, dy: f64, dz: f64) {
+    pub fn add_box(
+        &mut self,
+        (x, y, z): (f64, f64, f64),
+        dx: f64,
+        dy: f64,
+        dz: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
         let mut m = Matrix::new_polygon_matrix();
         m.add_box((x, y, z), dx, dy, dz);
-        self.render_polygons_with_stack(&m);
+        self.render_polygons_with_stack(&m, material)
     }
-    pub fn add_sphere(&mut self, center: (f64, f64, f64), radius: f64) {
+    pub fn add_sphere(
+        &mut self,
+        center: (f64, f64, f64),
+        radius: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
         let mut m = Matrix::new_polygon_matrix();
         m.add_sphere(center, radius);
-        self.render_polygons_with_stack(&m);
+        self.render_polygons_with_stack(&m, material)
     }
-    pub fn add_torus(&mut self, center: (f64, f64, f64), radius1: f64, radius2: f64) {
+    pub fn add_torus(
+        &mut self,
+        center: (f64, f64, f64),
+        radius1: f64,
+        radius2: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
         let mut m = Matrix::new_polygon_matrix();
         m.add_torus(center, radius1, radius2);
-        self.render_polygons_with_stack(&m);
+        self.render_polygons_with_stack(&m, material)
+    }
+    pub fn add_ellipsoid(
+        &mut self,
+        center: (f64, f64, f64),
+        (rx, ry, rz): (f64, f64, f64),
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_ellipsoid(center, rx, ry, rz);
+        self.render_polygons_with_stack(&m, material)
+    }
+    /// Draw a capped cylinder standing on `base`, see `Matrix::add_cylinder`
+    pub fn add_cylinder(
+        &mut self,
+        base: (f64, f64, f64),
+        radius: f64,
+        height: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_cylinder(base, radius, height);
+        self.render_polygons_with_stack(&m, material)
+    }
+    pub fn add_frustum(
+        &mut self,
+        base: (f64, f64, f64),
+        radius_bottom: f64,
+        radius_top: f64,
+        height: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_frustum(base, radius_bottom, radius_top, height);
+        self.render_polygons_with_stack(&m, material)
+    }
+    pub fn add_pyramid(
+        &mut self,
+        base: (f64, f64, f64),
+        side: f64,
+        height: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_pyramid(base, side, height);
+        self.render_polygons_with_stack(&m, material)
+    }
+    pub fn add_polyhedron(
+        &mut self,
+        polyhedron: Polyhedron,
+        center: (f64, f64, f64),
+        radius: f64,
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_polyhedron(polyhedron, center, radius);
+        self.render_polygons_with_stack(&m, material)
+    }
+    /// Draw a plane facing +z split into `columns` by `rows` squares, see `Matrix::add_plane`
+    pub fn add_plane(
+        &mut self,
+        corner: (f64, f64, f64),
+        width: f64,
+        height: f64,
+        (columns, rows): (usize, usize),
+        material: Option<&str>,
+    ) -> Result<(), DrawError> {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_plane(corner, width, height, columns, rows);
+        self.render_polygons_with_stack(&m, material)
+    }
+    /// Draw every face of the mesh at `filepath`, an STL file if it ends in `.stl`, otherwise OBJ
+    pub fn add_mesh(&mut self, filepath: &str, material: Option<&str>) -> io::Result<()> {
+        let mut m = Matrix::new_polygon_matrix();
+        if filepath.to_ascii_lowercase().ends_with(".stl") {
+            m.add_mesh_stl(filepath)?;
+        } else {
+            m.add_mesh_obj(filepath)?;
+        }
+        self.render_polygons_with_stack(&m, material)?;
+        Ok(())
     }
 }
 
@@ -137,7 +493,111 @@ impl Drawer {
         self.stack.push(self.get_top_matrix().clone());
     }
 
-    pub fn pop_matrix(&mut self) {
+    /// Pop the top of the stack, unless it is the last matrix left
+    pub fn pop_matrix(&mut self) -> Result<(), DrawError> {
+        if self.stack.len() <= 1 {
+            return Err(DrawError::EmptyStack);
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Save a copy of the current top of the stack as `name`
+    pub fn save_coord_system(&mut self, name: &str) {
+        let top = self.get_top_matrix().clone();
+        self.coord_systems.insert(name.to_string(), top);
+    }
+
+    pub fn get_coord_system(&self, name: &str) -> Option<&Matrix> {
+        self.coord_systems.get(name)
+    }
+
+    /// Draw in the coordinate system saved as `name` only for the drawing calls in `draw`
+    ///
+    /// Nothing is drawn if there is no coordinate system named `name`.
+    pub fn with_coord_system<R>(
+        &mut self,
+        name: &str,
+        draw: impl FnOnce(&mut Drawer) -> R,
+    ) -> Result<R, DrawError> {
+        let m = self
+            .coord_systems
+            .get(name)
+            .ok_or_else(|| DrawError::UnknownCoordSystem(name.to_string()))?
+            .clone();
+        self.stack.push(m);
+        let result = draw(self);
         self.stack.pop();
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::PPMImg;
+
+    #[test]
+    fn unknown_names() {
+        let mut drawer = Drawer::new(Box::new(PPMImg::new(10, 10, 255)));
+        assert_eq!(
+            Err(DrawError::UnknownMaterial(String::from("shiny"))),
+            drawer.add_sphere((5., 5., 0.), 3., Some("shiny"))
+        );
+        assert_eq!(
+            Err(DrawError::UnknownCoordSystem(String::from("c"))),
+            drawer.draw_line_across((0., 0., 0.), Some("c"), (9., 9., 0.), None)
+        );
+        assert_eq!(
+            Err(DrawError::UnknownCoordSystem(String::from("c"))),
+            drawer.with_coord_system("c", |_| ())
+        );
+        // nothing was drawn
+        let img = drawer.to_ppm();
+        assert!(img.pixels().iter().all(|&c| c == RGB::gray(0)));
+
+        drawer.save_coord_system("c");
+        assert_eq!(
+            Ok(Ok(())),
+            drawer.with_coord_system("c", |d| d.add_sphere((5., 5., 0.), 3., None))
+        );
+    }
+
+    #[test]
+    fn scene_size_scales_point_lights() {
+        use crate::graphics::golden::{compare, Tolerance};
+
+        let render = |size: u32| {
+            let mut drawer = Drawer::new(Box::new(PPMImg::new(size, size, 255)));
+            drawer.set_scene_size(500., 500.);
+            drawer.add_light(
+                "bulb",
+                Light::Point {
+                    pos: Vec3(100., 400., 300.),
+                    color: RGB::new(255, 200, 100),
+                },
+            );
+            drawer.add_sphere((250., 250., 0.), 200., None).unwrap();
+            drawer.to_ppm()
+        };
+        let (small, large) = (render(500), render(1000));
+        let downscaled: Vec<RGB> = (0..500 * 500)
+            .map(|i| large.pixels()[(i / 500) * 2 * 1000 + (i % 500) * 2])
+            .collect();
+        let downscaled = PPMImg::from_pixels(500, 500, 255, downscaled);
+
+        // only the edges between triangles come out differently
+        let c = compare(&small, &downscaled, Tolerance::default()).unwrap();
+        assert!(c.diff_pixels < 500 * 500 / 50, "{} pixels differ", c.diff_pixels);
+    }
+
+    #[test]
+    fn pop_keeps_last_matrix() {
+        let mut drawer = Drawer::new(Box::new(PPMImg::new(10, 10, 255)));
+        drawer.push_matrix();
+        assert_eq!(Ok(()), drawer.pop_matrix());
+        assert_eq!(Err(DrawError::EmptyStack), drawer.pop_matrix());
+        // still drawable
+        assert_eq!(Ok(()), drawer.add_sphere((5., 5., 0.), 3., None));
     }
 }
@@ -0,0 +1,175 @@
+//! Easing curves and keyframes for animating values
+
+use crate::graphics::utils;
+use std::f64::consts::PI;
+
+/// How a value moves from its start to its end
+///
+/// Every curve goes from 0 at `t = 0` to 1 at `t = 1`.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Cubic, starts slow
+    EaseIn,
+    /// Cubic, ends slow
+    EaseOut,
+    /// Cubic, starts and ends slow
+    EaseInOut,
+    /// Exponential, starts very slow
+    Exponential,
+    /// Half a cosine wave, starts and ends slow
+    Sine,
+    /// Holds the start value until the end
+    Step,
+    /// Cubic bezier timing curve from (0, 0) to (1, 1) with control points (x1, y1) and (x2, y2),
+    /// like CSS `cubic-bezier`. `x1` and `x2` should be in [0, 1].
+    Bezier(f64, f64, f64, f64),
+}
+
+impl Easing {
+    /// Returns the progress at time `t`, clamped to [0, 1]
+    pub fn ease(&self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1. - (1. - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (2. - 2. * t).powi(3) / 2.
+                }
+            }
+            Easing::Exponential => (2f64.powf(10. * t) - 1.) / 1023.,
+            Easing::Sine => (1. - (PI * t).cos()) / 2.,
+            Easing::Step => {
+                if t < 1. {
+                    0.
+                } else {
+                    1.
+                }
+            }
+            Easing::Bezier(x1, y1, x2, y2) => bezier_ease(t, (x1, y1), (x2, y2)),
+        }
+    }
+
+    /// Returns the value between `start` and `end` at time `t`
+    pub fn interpolate(&self, start: f64, end: f64, t: f64) -> f64 {
+        start + (end - start) * self.ease(t)
+    }
+}
+
+/// Finds the point on the bezier curve whose x is `t` and returns its y
+fn bezier_ease(t: f64, p1: (f64, f64), p2: (f64, f64)) -> f64 {
+    let (ax, bx, cx, dx) = utils::compute_bezier3_coef(0., p1.0, p2.0, 1.);
+    let (ay, by, cy, dy) = utils::compute_bezier3_coef(0., p1.1, p2.1, 1.);
+    let x = |s: f64| ((ax * s + bx) * s + cx) * s + dx;
+
+    // x is increasing in s when x1 and x2 are in [0, 1], so bisection always finds it
+    let (mut lo, mut hi) = (0., 1.);
+    let mut s = t;
+    for _ in 0..64 {
+        if x(s) < t {
+            lo = s;
+        } else {
+            hi = s;
+        }
+        s = (lo + hi) / 2.;
+    }
+    ((ay * s + by) * s + cy) * s + dy
+}
+
+/// A value set at several frames, eased in between
+///
+/// The easing of a key is used from that key to the next one.
+/// Before the first key and after the last the value holds.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Keyframes {
+    /// (frame, value, easing), sorted by frame
+    keys: Vec<(f64, f64, Easing)>,
+}
+
+impl Keyframes {
+    /// Set the value at `frame`, replacing any key already there
+    pub fn add_key(&mut self, frame: f64, value: f64, easing: Easing) {
+        match self.keys.iter().position(|&(f, _, _)| f >= frame) {
+            Some(i) if self.keys[i].0 == frame => self.keys[i] = (frame, value, easing),
+            Some(i) => self.keys.insert(i, (frame, value, easing)),
+            None => self.keys.push((frame, value, easing)),
+        }
+    }
+
+    /// Returns the value at `frame`, or `None` if there are no keys
+    pub fn value_at(&self, frame: f64) -> Option<f64> {
+        let first = self.keys.first()?;
+        if frame <= first.0 {
+            return Some(first.1);
+        }
+        for pair in self.keys.windows(2) {
+            let ((f0, v0, easing), (f1, v1, _)) = (pair[0], pair[1]);
+            if frame <= f1 {
+                return Some(easing.interpolate(v0, v1, (frame - f0) / (f1 - f0)));
+            }
+        }
+        self.keys.last().map(|&(_, v, _)| v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn curves_start_at_0_and_end_at_1() {
+        let curves = [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::Exponential,
+            Easing::Sine,
+            Easing::Step,
+            Easing::Bezier(0.25, 0.1, 0.25, 1.),
+        ];
+        for easing in curves.iter() {
+            assert!(close(0., easing.ease(0.)), "{:?}", easing);
+            assert!(close(1., easing.ease(1.)), "{:?}", easing);
+        }
+        assert_eq!(0., Easing::Step.ease(0.99));
+        assert!(close(0.125, Easing::EaseIn.ease(0.5)));
+        assert!(close(0.5, Easing::EaseInOut.ease(0.5)));
+        assert!(close(0.5, Easing::Sine.ease(0.5)));
+    }
+
+    #[test]
+    fn bezier() {
+        // control points on the diagonal make a straight line
+        let linear = Easing::Bezier(1. / 3., 1. / 3., 2. / 3., 2. / 3.);
+        for i in 0..=10 {
+            let t = i as f64 / 10.;
+            assert!((linear.ease(t) - t).abs() < 1e-6);
+        }
+        let ease_in = Easing::Bezier(0.42, 0., 1., 1.);
+        assert!(ease_in.ease(0.25) < 0.25);
+    }
+
+    #[test]
+    fn keyframes() {
+        let mut k = Keyframes::default();
+        assert_eq!(None, k.value_at(0.));
+        k.add_key(10., 100., Easing::Linear);
+        k.add_key(0., 0., Easing::Step);
+        k.add_key(20., 0., Easing::Linear);
+        assert_eq!(Some(0.), k.value_at(-5.));
+        assert_eq!(Some(0.), k.value_at(9.));
+        assert_eq!(Some(100.), k.value_at(10.));
+        assert_eq!(Some(50.), k.value_at(15.));
+        assert_eq!(Some(0.), k.value_at(25.));
+    }
+}
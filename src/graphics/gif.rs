@@ -0,0 +1,598 @@
+//! Animated GIF writer, so animations don't need imagemagick
+//!
+//! Every frame gets its own palette of at most 256 colors, reduced with median cut
+//! if the frame has more colors than that.
+
+use crate::graphics::{Canvas, PPMImg};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+/// How many times an animation plays
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    Once,
+    Forever,
+}
+
+/// Writes frames to an animated GIF as they are added
+///
+/// Call `finish` after the last frame to end the file.
+pub struct GifEncoder<W: Write> {
+    writer: W,
+    width: u16,
+    height: u16,
+    /// Delay after each frame, in hundredths of a second
+    delay: u16,
+    repeat: Repeat,
+    /// Make pixels that didn't change since the last frame transparent
+    diff_transparency: bool,
+    /// Colors shown after the last frame
+    shown: Option<Vec<[u8; 3]>>,
+    header_written: bool,
+}
+
+impl GifEncoder<BufWriter<File>> {
+    /// Create a GIF at `filepath`
+    pub fn create(filepath: &str, width: u32, height: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(filepath)?), width, height)
+    }
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Create an encoder for frames of `width` by `height`
+    ///
+    /// Defaults to 5/100 s between frames, looping forever, without frame-diff transparency.
+    pub fn new(writer: W, width: u32, height: u32) -> io::Result<Self> {
+        let size = |n: u32| {
+            u16::try_from(n).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("GIFs can be at most 65535 pixels wide or tall, got {}", n),
+                )
+            })
+        };
+        Ok(GifEncoder {
+            writer,
+            width: size(width)?,
+            height: size(height)?,
+            delay: 5,
+            repeat: Repeat::Forever,
+            diff_transparency: false,
+            shown: None,
+            header_written: false,
+        })
+    }
+
+    /// Set the delay after each following frame, in hundredths of a second
+    pub fn set_delay(&mut self, delay: u16) {
+        self.delay = delay;
+    }
+
+    /// Set how many times the animation plays. Must be called before the first frame.
+    pub fn set_repeat(&mut self, repeat: Repeat) {
+        self.repeat = repeat;
+    }
+
+    /// Only store the pixels that changed since the last frame, the rest is transparent
+    ///
+    /// This makes animations with a still background a lot smaller.
+    pub fn set_diff_transparency(&mut self, on: bool) {
+        self.diff_transparency = on;
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let w = &mut self.writer;
+        w.write_all(b"GIF89a")?;
+        // no global color table, every frame has its own
+        w.write_all(&self.width.to_le_bytes())?;
+        w.write_all(&self.height.to_le_bytes())?;
+        w.write_all(&[0, 0, 0])?;
+
+        if self.repeat == Repeat::Forever {
+            w.write_all(&[0x21, 0xff, 11])?;
+            w.write_all(b"NETSCAPE2.0")?;
+            // a loop count of 0 repeats forever
+            w.write_all(&[3, 1, 0, 0, 0])?;
+        }
+        self.header_written = true;
+        Ok(())
+    }
+
+    /// Add `img` as the next frame
+    pub fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        if img.width() != self.width as u32 || img.height() != self.height as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame is {}x{}, but the GIF is {}x{}",
+                    img.width(),
+                    img.height(),
+                    self.width,
+                    self.height
+                ),
+            ));
+        }
+        if !self.header_written {
+            self.write_header()?;
+        }
+
+        let depth = img.depth() as u32;
+        let colors: Vec<[u8; 3]> = img
+            .pixels()
+            .iter()
+            .map(|c| {
+                let scale = |c: u16| ((c as u32 * 255 + depth / 2) / depth).min(255) as u8;
+                [scale(c.red), scale(c.green), scale(c.blue)]
+            })
+            .collect();
+
+        // pixels that need to be drawn, and the part of the frame they are in
+        let width = self.width as usize;
+        let changed: Vec<bool> = match (&self.shown, self.diff_transparency) {
+            (Some(shown), true) => colors.iter().zip(shown).map(|(a, b)| a != b).collect(),
+            _ => vec![true; colors.len()],
+        };
+        let (mut left, mut top, mut right, mut bottom) = (width, self.height as usize, 0, 0);
+        for (i, _) in changed.iter().enumerate().filter(|(_, &c)| c) {
+            let (x, y) = (i % width, i / width);
+            left = left.min(x);
+            right = right.max(x + 1);
+            top = top.min(y);
+            bottom = bottom.max(y + 1);
+        }
+        if left >= right {
+            // nothing changed, draw one transparent pixel
+            left = 0;
+            top = 0;
+            right = 1;
+            bottom = 1;
+        }
+
+        let transparent = self.shown.is_some() && self.diff_transparency;
+        let max_colors = if transparent { 255 } else { 256 };
+        let mut histogram = HashMap::new();
+        for y in top..bottom {
+            for x in left..right {
+                let i = y * width + x;
+                if changed[i] {
+                    *histogram.entry(colors[i]).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut palette = median_cut(histogram.into_iter().collect(), max_colors);
+        let transparent_index = palette.len();
+        if transparent {
+            palette.push([0, 0, 0]);
+        }
+
+        let shown = self.shown.get_or_insert_with(|| vec![[0; 3]; colors.len()]);
+        let mut nearest_cache = HashMap::new();
+        let mut indices = Vec::with_capacity((right - left) * (bottom - top));
+        for y in top..bottom {
+            for x in left..right {
+                let i = y * width + x;
+                if !changed[i] {
+                    indices.push(transparent_index as u8);
+                    continue;
+                }
+                let index = *nearest_cache
+                    .entry(colors[i])
+                    .or_insert_with(|| nearest(&palette[..transparent_index], colors[i]));
+                shown[i] = palette[index as usize];
+                indices.push(index);
+            }
+        }
+
+        // color tables have 2^n entries
+        let bits = (1..=8).find(|b| 1 << b >= palette.len()).unwrap();
+
+        let w = &mut self.writer;
+        // graphic control extension: keep the last frame under this one
+        w.write_all(&[0x21, 0xf9, 4, (1 << 2) | transparent as u8])?;
+        w.write_all(&self.delay.to_le_bytes())?;
+        w.write_all(&[
+            if transparent {
+                transparent_index as u8
+            } else {
+                0
+            },
+            0,
+        ])?;
+
+        // image descriptor with a local color table
+        w.write_all(&[0x2c])?;
+        for n in [left, top, right - left, bottom - top].iter() {
+            w.write_all(&(*n as u16).to_le_bytes())?;
+        }
+        w.write_all(&[0x80 | (bits - 1)])?;
+        for i in 0..1 << bits {
+            w.write_all(palette.get(i).unwrap_or(&[0; 3]))?;
+        }
+
+        let min_code_size = bits.max(2);
+        w.write_all(&[min_code_size])?;
+        for block in lzw_encode(min_code_size, &indices).chunks(255) {
+            w.write_all(&[block.len() as u8])?;
+            w.write_all(block)?;
+        }
+        w.write_all(&[0])
+    }
+
+    /// End the file and return the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.header_written {
+            self.write_header()?;
+        }
+        self.writer.write_all(&[0x3b])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Save `frames` as an animated GIF at `filepath`, with `delay` hundredths of a second between frames
+pub fn save_gif(filepath: &str, frames: &[PPMImg], delay: u16, repeat: Repeat) -> io::Result<()> {
+    let (width, height) = frames
+        .first()
+        .map(|f| (f.width(), f.height()))
+        .unwrap_or((1, 1));
+    let mut encoder = GifEncoder::create(filepath, width, height)?;
+    encoder.set_delay(delay);
+    encoder.set_repeat(repeat);
+    for frame in frames {
+        encoder.add_frame(frame)?;
+    }
+    encoder.finish().map(|_| ())
+}
+
+/// Reduce `colors` (color, pixel count) to at most `max` colors
+fn median_cut(colors: Vec<([u8; 3], u32)>, max: usize) -> Vec<[u8; 3]> {
+    if colors.len() <= max {
+        return colors.into_iter().map(|(c, _)| c).collect();
+    }
+
+    // the channel with the widest range in a box, and that range
+    let widest = |b: &[([u8; 3], u32)]| {
+        (0..3)
+            .map(|ch| {
+                let (lo, hi) = b
+                    .iter()
+                    .fold((255, 0), |(lo, hi), (c, _)| (c[ch].min(lo), c[ch].max(hi)));
+                (ch, hi - lo)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap()
+    };
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max {
+        // split the box with the widest range
+        let (i, (ch, range)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (i, widest(b)))
+            .max_by_key(|&(_, (_, range))| range)
+            .unwrap();
+        if range == 0 {
+            break;
+        }
+
+        let mut b = boxes.swap_remove(i);
+        b.sort_unstable_by_key(|(c, _)| c[ch]);
+        let total: u32 = b.iter().map(|(_, n)| n).sum();
+        let mut count = 0;
+        let median = b
+            .iter()
+            .position(|(_, n)| {
+                count += n;
+                count * 2 >= total
+            })
+            .unwrap();
+        // both halves need at least one color
+        let split = (median + 1).min(b.len() - 1);
+        let upper = b.split_off(split);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let total: u64 = b.iter().map(|&(_, n)| n as u64).sum();
+            let mut avg = [0; 3];
+            for (ch, a) in avg.iter_mut().enumerate() {
+                let sum: u64 = b.iter().map(|(c, n)| c[ch] as u64 * *n as u64).sum();
+                *a = ((sum + total / 2) / total) as u8;
+            }
+            avg
+        })
+        .collect()
+}
+
+/// Index of the color in `palette` closest to `color`
+fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let dist = |p: &[u8; 3]| {
+        (0..3)
+            .map(|ch| (p[ch] as i32 - color[ch] as i32).pow(2))
+            .sum::<i32>()
+    };
+    (0..palette.len())
+        .min_by_key(|&i| dist(&palette[i]))
+        .unwrap_or(0) as u8
+}
+
+/// Packs codes of varying width into bytes, least significant bit first
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.acc |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compress color `indices` with GIF's variant of LZW
+fn lzw_encode(min_code_size: u8, indices: &[u8]) -> Vec<u8> {
+    const MAX_CODES: u16 = 4096;
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = BitWriter {
+        bytes: vec![],
+        acc: 0,
+        bits: 0,
+    };
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    out.write(clear, code_size);
+    let mut iter = indices.iter();
+    let mut prefix = match iter.next() {
+        Some(&i) => i as u16,
+        None => {
+            out.write(end, code_size);
+            return out.finish();
+        }
+    };
+    for &k in iter {
+        if let Some(&code) = table.get(&(prefix, k)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, code_size);
+        if next_code < MAX_CODES {
+            table.insert((prefix, k), next_code);
+            next_code += 1;
+            // the decoder adds its codes one step later, so it widens one code later too
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            out.write(clear, code_size);
+            table.clear();
+            next_code = end + 1;
+            code_size = min_code_size + 1;
+        }
+        prefix = k as u16;
+    }
+    out.write(prefix, code_size);
+    out.write(end, code_size);
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    /// Decode GIF LZW data
+    fn lzw_decode(min_code_size: u8, data: &[u8]) -> Vec<u8> {
+        let clear = 1u16 << min_code_size;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> { (0..clear + 2).map(|i| vec![i as u8]).collect() };
+        let mut table = reset();
+        let mut code_size = min_code_size + 1;
+        let (mut acc, mut bits, mut bytes) = (0u32, 0, data.iter());
+        let mut prev: Option<Vec<u8>> = None;
+        let mut out = vec![];
+        loop {
+            while bits < code_size {
+                acc |= (*bytes.next().expect("missing end code") as u32) << bits;
+                bits += 8;
+            }
+            let code = (acc & ((1 << code_size) - 1)) as u16;
+            acc >>= code_size;
+            bits -= code_size;
+
+            if code == clear {
+                table = reset();
+                code_size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code as usize), &prev) {
+                (Some(e), _) => e.clone(),
+                (None, Some(p)) => {
+                    let mut e = p.clone();
+                    e.push(p[0]);
+                    e
+                }
+                (None, None) => panic!("bad code {}", code),
+            };
+            if let Some(mut p) = prev {
+                if table.len() < 4096 {
+                    p.push(entry[0]);
+                    table.push(p);
+                    if table.len() == 1 << code_size && code_size < 12 {
+                        code_size += 1;
+                    }
+                }
+            }
+            out.extend(&entry);
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let short = vec![1, 1, 1, 2, 0, 3, 3, 3, 3, 1];
+        assert_eq!(short, lzw_decode(2, &lzw_encode(2, &short)));
+
+        // enough varied data to fill the code table several times
+        let long: Vec<u8> = (0..200_000u32)
+            .map(|i| ((i * 7919) ^ (i >> 5)) as u8)
+            .collect();
+        assert_eq!(long, lzw_decode(8, &lzw_encode(8, &long)));
+        assert!(lzw_decode(2, &lzw_encode(2, &[])).is_empty());
+    }
+
+    #[test]
+    fn median_cut_limits_colors() {
+        let colors: Vec<([u8; 3], u32)> = (0..1000u32)
+            .map(|i| ([i as u8, (i / 4) as u8, 255 - i as u8], 1 + i % 3))
+            .collect();
+        let palette = median_cut(colors, 256);
+        assert_eq!(256, palette.len());
+
+        let few = vec![([1, 2, 3], 10), ([4, 5, 6], 1)];
+        assert_eq!(vec![[1, 2, 3], [4, 5, 6]], median_cut(few, 256));
+    }
+
+    /// Frames of a GIF as (left, top, width, height, transparent index, colors of each pixel)
+    #[allow(clippy::type_complexity)]
+    fn frames(gif: &[u8]) -> Vec<(u16, u16, u16, u16, Option<u8>, Vec<[u8; 3]>)> {
+        let u16_at = |i: usize| u16::from_le_bytes([gif[i], gif[i + 1]]);
+        assert_eq!(b"GIF89a", &gif[..6]);
+        let mut i = 13;
+        let mut transparent = None;
+        let mut frames = vec![];
+        loop {
+            match gif[i] {
+                0x21 => {
+                    if gif[i + 1] == 0xf9 {
+                        transparent = if gif[i + 3] & 1 == 1 {
+                            Some(gif[i + 6])
+                        } else {
+                            None
+                        };
+                    }
+                    i += 2;
+                    while gif[i] != 0 {
+                        i += gif[i] as usize + 1;
+                    }
+                    i += 1;
+                }
+                0x2c => {
+                    let (left, top, w, h) =
+                        (u16_at(i + 1), u16_at(i + 3), u16_at(i + 5), u16_at(i + 7));
+                    let table_len = 1 << ((gif[i + 9] & 7) + 1);
+                    let table = &gif[i + 10..i + 10 + 3 * table_len];
+                    i += 10 + 3 * table_len;
+                    let min_code_size = gif[i];
+                    i += 1;
+                    let mut data = vec![];
+                    while gif[i] != 0 {
+                        data.extend(&gif[i + 1..i + 1 + gif[i] as usize]);
+                        i += gif[i] as usize + 1;
+                    }
+                    i += 1;
+                    let pixels = lzw_decode(min_code_size, &data)
+                        .into_iter()
+                        .map(|p| {
+                            let p = p as usize * 3;
+                            [table[p], table[p + 1], table[p + 2]]
+                        })
+                        .collect();
+                    frames.push((left, top, w, h, transparent, pixels));
+                }
+                0x3b => return frames,
+                b => panic!("unexpected block {:x}", b),
+            }
+        }
+    }
+
+    #[test]
+    fn animation() {
+        let mut img = PPMImg::new(4, 6, 255);
+        img.set_fg_color(RGB::new(255, 0, 0));
+        img.plot(1, 1, 0.);
+
+        let mut encoder = GifEncoder::new(vec![], 6, 4).unwrap();
+        encoder.set_diff_transparency(true);
+        encoder.set_repeat(Repeat::Forever);
+        encoder.add_frame(&img).unwrap();
+        img.set_fg_color(RGB::new(0, 0, 255));
+        img.plot(4, 2, 0.);
+        encoder.add_frame(&img).unwrap();
+        encoder.add_frame(&img).unwrap();
+        let gif = encoder.finish().unwrap();
+
+        let frames = frames(&gif);
+        assert_eq!(3, frames.len());
+
+        let (_, _, w, h, transparent, pixels) = &frames[0];
+        assert_eq!((6, 4, None), (*w, *h, *transparent));
+        // the origin is at the bottom left, so (1, 1) is on row 2 from the top
+        assert_eq!([255, 0, 0], pixels[2 * 6 + 1]);
+        assert_eq!([0, 0, 0], pixels[0]);
+
+        // only the changed pixel, which is at row 1 from the top
+        let (left, top, w, h, _, pixels) = &frames[1];
+        assert_eq!((4, 1, 1, 1), (*left, *top, *w, *h));
+        assert_eq!(vec![[0, 0, 255]], *pixels);
+
+        // nothing changed
+        assert_eq!((1, 1), (frames[2].2, frames[2].3));
+    }
+
+    #[test]
+    fn many_colors() {
+        let mut img = PPMImg::new(64, 64, 65535);
+        for x in 0..64 {
+            for y in 0..64 {
+                img.set_fg_color(RGB::new(x as u16 * 1024, y as u16 * 1024, 40000));
+                img.plot(x, y, 0.);
+            }
+        }
+        let mut encoder = GifEncoder::new(vec![], 64, 64).unwrap();
+        encoder.add_frame(&img).unwrap();
+        let frames = frames(&encoder.finish().unwrap());
+
+        // 4096 colors get reduced, but stay close
+        let pixels = &frames[0].5;
+        assert_eq!(64 * 64, pixels.len());
+        let bottom_right = pixels[63 * 64 + 63];
+        assert!((bottom_right[0] as i32 - 252).abs() <= 8);
+        assert!((bottom_right[1] as i32).abs() <= 8);
+    }
+
+    #[test]
+    fn frame_size_must_match() {
+        let mut encoder = GifEncoder::new(vec![], 10, 10).unwrap();
+        assert!(encoder.add_frame(&PPMImg::new(5, 10, 255)).is_err());
+        assert!(GifEncoder::new(vec![], 70000, 1).is_err());
+    }
+}
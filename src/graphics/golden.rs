@@ -0,0 +1,231 @@
+//! Golden image tests: compare a rendered image with a checked-in reference
+//!
+//! References live in `tests/golden/<name>.png`. When an image doesn't match,
+//! the rendered image and a diff are saved in `target/golden/` and the test fails.
+//!
+//! Run the tests with `BLESS=1` to save the rendered images as the new references:
+//!
+//! ```text
+//! BLESS=1 cargo test
+//! ```
+
+use crate::graphics::{
+    light::LightConfig,
+    material::Material,
+    render::RenderOptions,
+    Canvas, Matrix, PPMImg, RGB,
+};
+use std::{env, fs, path::PathBuf};
+
+/// Set to save rendered images as the references instead of comparing them
+pub const BLESS_VAR: &str = "BLESS";
+
+/// How different two images can be and still match
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tolerance {
+    /// Largest difference in any channel, out of 255, for pixels to count as the same
+    pub channel: u16,
+    /// Number of pixels allowed to be different
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    /// Allows for a few pixels to come out differently from rounding
+    fn default() -> Self {
+        Tolerance {
+            channel: 2,
+            pixels: 8,
+        }
+    }
+}
+
+impl Tolerance {
+    pub fn exact() -> Self {
+        Tolerance {
+            channel: 0,
+            pixels: 0,
+        }
+    }
+}
+
+/// Result of comparing two images of the same size
+pub struct Comparison {
+    /// Number of pixels that differ by more than the channel tolerance
+    pub diff_pixels: usize,
+    /// Largest channel difference, out of 255
+    pub max_diff: u16,
+    /// Differing pixels in red over a dimmed copy of the expected image
+    pub diff_image: PPMImg,
+}
+
+/// Channels of `c` scaled to 0-255
+fn to_8bit(c: RGB, depth: u16) -> [u16; 3] {
+    let scale = |v: u16| ((v as u32 * 255 + depth as u32 / 2) / depth as u32) as u16;
+    [scale(c.red), scale(c.green), scale(c.blue)]
+}
+
+/// Compare `actual` with `expected`, or return `None` if their sizes differ
+pub fn compare(expected: &PPMImg, actual: &PPMImg, tolerance: Tolerance) -> Option<Comparison> {
+    if (expected.width(), expected.height()) != (actual.width(), actual.height()) {
+        return None;
+    }
+
+    let mut diff_pixels = 0;
+    let mut max_diff = 0;
+    let diff = expected
+        .pixels()
+        .iter()
+        .zip(actual.pixels())
+        .map(|(&e, &a)| {
+            let (e, a) = (to_8bit(e, expected.depth()), to_8bit(a, actual.depth()));
+            let d = (0..3)
+                .map(|ch| (e[ch] as i32 - a[ch] as i32).unsigned_abs() as u16)
+                .max()
+                .unwrap();
+            max_diff = max_diff.max(d);
+            if d > tolerance.channel {
+                diff_pixels += 1;
+                RGB::new(255, 0, 0)
+            } else {
+                RGB::gray((e[0] + e[1] + e[2]) / 3 / 4)
+            }
+        })
+        .collect();
+
+    Some(Comparison {
+        diff_pixels,
+        max_diff,
+        diff_image: PPMImg::from_pixels(expected.height(), expected.width(), 255, diff),
+    })
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn output_path(name: &str, kind: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+    fs::create_dir_all(&dir).expect("Error creating target/golden");
+    dir.join(format!("{}.{}.png", name, kind))
+}
+
+fn blessing() -> bool {
+    env::var_os(BLESS_VAR).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Assert that `img` matches the reference image `name` with the default tolerance
+pub fn assert_golden(name: &str, img: &PPMImg) {
+    assert_golden_with(name, img, Tolerance::default());
+}
+
+/// Assert that `img` matches the reference image `name`
+pub fn assert_golden_with(name: &str, img: &PPMImg, tolerance: Tolerance) {
+    let reference = reference_path(name);
+    if blessing() {
+        fs::create_dir_all(reference.parent().unwrap()).expect("Error creating tests/golden");
+        img.save(&reference.to_string_lossy())
+            .expect("Error saving reference image");
+        return;
+    }
+
+    let save_actual = || {
+        let path = output_path(name, "actual");
+        img.save(&path.to_string_lossy())
+            .expect("Error saving rendered image");
+        path
+    };
+    let expected = PPMImg::load(&reference.to_string_lossy()).unwrap_or_else(|e| {
+        panic!(
+            "Can't read reference image {}: {}\nRendered image is in {}, run with {}=1 to save it as the reference",
+            reference.display(),
+            e,
+            save_actual().display(),
+            BLESS_VAR
+        )
+    });
+
+    let comparison = compare(&expected, img, tolerance).unwrap_or_else(|| {
+        panic!(
+            "{}: rendered image is {}x{}, but the reference is {}x{}",
+            name,
+            img.width(),
+            img.height(),
+            expected.width(),
+            expected.height()
+        )
+    });
+    if comparison.diff_pixels > tolerance.pixels {
+        let diff_path = output_path(name, "diff");
+        comparison
+            .diff_image
+            .save(&diff_path.to_string_lossy())
+            .expect("Error saving diff image");
+        panic!(
+            "{}: {} pixels differ by up to {} (tolerance: {} pixels by {})\nrendered: {}\ndiff: {}\nrun with {}=1 if the new image is correct",
+            name,
+            comparison.diff_pixels,
+            comparison.max_diff,
+            tolerance.pixels,
+            tolerance.channel,
+            save_actual().display(),
+            diff_path.display(),
+            BLESS_VAR
+        );
+    }
+}
+
+/// Render polygon matrix `m` with the default lighting and assert it matches the reference `name`
+pub fn assert_polygon_matrix(name: &str, m: &Matrix) {
+    let mut img = PPMImg::new(500, 500, 255);
+    img.render_polygon_matrix(
+        m,
+        &LightConfig::default(),
+        &Material::default(),
+        &RenderOptions::default(),
+    );
+    assert_golden(name, &img);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance() {
+        let expected = PPMImg::from_pixels(
+            1,
+            3,
+            255,
+            vec![RGB::gray(0), RGB::gray(100), RGB::gray(200)],
+        );
+        let actual = PPMImg::from_pixels(
+            1,
+            3,
+            255,
+            vec![RGB::gray(2), RGB::gray(100), RGB::new(200, 210, 200)],
+        );
+
+        let c = compare(&expected, &actual, Tolerance::default()).unwrap();
+        assert_eq!((1, 10), (c.diff_pixels, c.max_diff));
+        assert_eq!(RGB::new(255, 0, 0), c.diff_image.pixels()[2]);
+        assert_eq!(RGB::gray(0), c.diff_image.pixels()[0]);
+
+        let c = compare(&expected, &actual, Tolerance::exact()).unwrap();
+        assert_eq!(2, c.diff_pixels);
+    }
+
+    #[test]
+    fn compares_across_depths() {
+        let expected = PPMImg::from_pixels(1, 1, 255, vec![RGB::gray(255)]);
+        let actual = PPMImg::from_pixels(1, 1, 65535, vec![RGB::gray(65535)]);
+        assert_eq!(
+            0,
+            compare(&expected, &actual, Tolerance::exact())
+                .unwrap()
+                .diff_pixels
+        );
+        assert!(compare(&expected, &PPMImg::new(2, 1, 255), Tolerance::default()).is_none());
+    }
+}
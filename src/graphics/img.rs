@@ -2,12 +2,52 @@ use std::convert::TryInto;
 
 use std::{
     fmt::Debug,
-    io::{self, prelude::Write},
+    fs::File,
+    io::{self, prelude::Read, prelude::Write, BufReader},
 };
 // internal use
-use crate::graphics::{processes::pipe_to_magick, utils, Canvas, RGB};
+use crate::graphics::{
+    gif::{self, Repeat},
+    png, utils, Canvas, RGB,
+};
 use io::BufWriter;
 
+/// Image formats images can be saved in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Gif,
+}
+
+impl ImageFormat {
+    /// Returns the format named `name`, like `png`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "gif" => Some(ImageFormat::Gif),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the file at `filepath` from its extension
+    pub fn from_path(filepath: &str) -> Option<Self> {
+        std::path::Path::new(filepath)
+            .extension()
+            .and_then(|ext| Self::from_name(&ext.to_string_lossy()))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => "gif",
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct PPMImg {
     height: u32,
     width: u32,
@@ -61,20 +101,51 @@ impl PPMImg {
             invert_y: false,
             fg_color: RGB::gray(depth),
             bg_color,
-            data: vec![bg_color; (width * height).try_into().unwrap()],
-            zbuf: vec![f64::NEG_INFINITY; (width * height).try_into().unwrap()],
+            data: vec![bg_color; width as usize * height as usize],
+            zbuf: vec![f64::NEG_INFINITY; width as usize * height as usize],
+        }
+    }
+
+    /// Create a PPMImg from the colors of its pixels, row by row starting from the top left
+    pub fn from_pixels(height: u32, width: u32, depth: u16, pixels: Vec<RGB>) -> PPMImg {
+        let len = width as usize * height as usize;
+        assert_eq!(
+            len,
+            pixels.len(),
+            "Wrong number of pixels for a {} by {} image",
+            width,
+            height
+        );
+        PPMImg {
+            height,
+            width,
+            depth,
+            x_wrap: false,
+            y_wrap: false,
+            invert_y: false,
+            fg_color: RGB::gray(depth),
+            bg_color: RGB::gray(0),
+            data: pixels,
+            zbuf: vec![f64::NEG_INFINITY; len],
         }
     }
 
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    /// Colors of the pixels, row by row starting from the top left
+    pub fn pixels(&self) -> &[RGB] {
+        &self.data
+    }
+
     pub fn write_bin_to_buf(&self, writer: &mut dyn Write) -> io::Result<()> {
         let mut buf = BufWriter::new(writer);
         writeln!(buf, "P6")?;
         writeln!(buf, "{} {} {}", self.width, self.height, self.depth)?;
         if self.depth < 256 {
             for t in self.data.iter() {
-                buf.write(&[t.green as u8])?;
-                buf.write(&[t.green as u8])?;
-                buf.write(&[t.blue as u8])?;
+                buf.write_all(&[t.red as u8, t.green as u8, t.blue as u8])?;
             }
         } else {
             for t in self.data.iter() {
@@ -87,18 +158,200 @@ impl PPMImg {
         buf.flush()?;
         Ok(())
     }
+
+    /// Save the image to `filepath` as `format`, whatever its extension
+    pub fn save_as(&self, filepath: &str, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_binary(filepath),
+            ImageFormat::Png => png::save(self, filepath),
+            ImageFormat::Gif => {
+                gif::save_gif(filepath, std::slice::from_ref(self), 0, Repeat::Once)
+            }
+        }
+    }
+
     pub fn write_binary(&self, filepath: &str) -> io::Result<()> {
         self.write_bin_to_buf(&mut utils::create_file(filepath))
     }
-    pub fn write_ascii(&self, filepath: &str) -> io::Result<()> {
-        let mut file = BufWriter::new(utils::create_file(filepath));
-        writeln!(file, "P3")?;
-        writeln!(file, "{} {} {}", self.width, self.height, self.depth)?;
-        for t in self.data.iter() {
-            writeln!(file, "{} {} {}", t.red, t.green, t.blue)?;
+}
+
+// importer
+impl PPMImg {
+    /// Read a PPM, PGM or PBM image (P1 to P6)
+    ///
+    /// Grayscale and bitmap images are converted to RGB. The depth is the maxval of the file,
+    /// or 1 for bitmaps, where white is 1 and black is 0.
+    pub fn from_reader(reader: &mut dyn Read) -> io::Result<PPMImg> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let mut p = PnmParser {
+            bytes: &bytes,
+            pos: 0,
+        };
+
+        let magic = p.token("magic number")?;
+        let kind = match magic.as_str() {
+            "P1" | "P2" | "P3" | "P4" | "P5" | "P6" => magic.as_bytes()[1] - b'0',
+            _ => return Err(invalid_pnm(format!("unknown magic number {:?}", magic))),
+        };
+        let width = p.number("width")?;
+        let height = p.number("height")?;
+        let bitmap = kind == 1 || kind == 4;
+        let maxval = if bitmap { 1 } else { p.number("maxval")? };
+        if maxval == 0 || maxval > 65535 {
+            return Err(invalid_pnm(format!(
+                "maxval must be between 1 and 65535, got {}",
+                maxval
+            )));
         }
-        file.flush()?;
-        Ok(())
+        if width == 0 || height == 0 {
+            return Err(invalid_pnm(String::from("image has no pixels")));
+        }
+        let too_large = || invalid_pnm(format!("image is too large: {}x{}", width, height));
+        let count = (width as usize)
+            .checked_mul(height as usize)
+            .filter(|&n| n <= u32::MAX as usize)
+            .ok_or_else(too_large)?;
+        let channels = if kind == 3 || kind == 6 { 3 } else { 1 };
+        let len = count.checked_mul(channels).ok_or_else(too_large)?;
+
+        // ASCII samples grow as they are read, so a header alone can't reserve a huge buffer
+        let mut samples = Vec::new();
+        match kind {
+            1 => {
+                // digits don't need to be separated
+                while samples.len() < count {
+                    p.skip_space();
+                    match p.bytes.get(p.pos) {
+                        Some(b'0') => samples.push(1),
+                        Some(b'1') => samples.push(0),
+                        Some(&b) => {
+                            return Err(invalid_pnm(format!(
+                                "expected 0 or 1, found {:?}",
+                                b as char
+                            )))
+                        }
+                        None => return Err(not_enough_data()),
+                    }
+                    p.pos += 1;
+                }
+            }
+            2 | 3 => {
+                for _ in 0..len {
+                    samples.push(p.number("pixel value")?);
+                }
+            }
+            _ => {
+                // a single whitespace separates the header from binary data
+                p.pos += 1;
+                let data = p.bytes.get(p.pos..).unwrap_or(&[]);
+                let sample_len = if maxval < 256 { 1 } else { 2 };
+                let row_len = if kind == 4 {
+                    Some((width as usize).div_ceil(8))
+                } else {
+                    (width as usize)
+                        .checked_mul(channels)
+                        .and_then(|n| n.checked_mul(sample_len))
+                };
+                let data_len = row_len
+                    .and_then(|n| n.checked_mul(height as usize))
+                    .ok_or_else(too_large)?;
+                // the data has to be there before reserving room for its samples
+                let data = data.get(..data_len).ok_or_else(not_enough_data)?;
+                samples.reserve_exact(len);
+                if kind == 4 {
+                    let row_len = data_len / height as usize;
+                    for row in data.chunks(row_len).take(height as usize) {
+                        for x in 0..width as usize {
+                            let black = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                            samples.push(!black as u32);
+                        }
+                    }
+                } else if maxval < 256 {
+                    samples.extend(data.iter().map(|&b| b as u32));
+                } else {
+                    samples.extend(
+                        data.chunks(2)
+                            .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32),
+                    );
+                }
+            }
+        }
+
+        if let Some(&s) = samples.iter().find(|&&s| s > maxval) {
+            return Err(invalid_pnm(format!(
+                "pixel value {} is larger than maxval {}",
+                s, maxval
+            )));
+        }
+        let pixels = samples
+            .chunks(channels)
+            .map(|c| match *c {
+                [r, g, b] => RGB::new(r as u16, g as u16, b as u16),
+                _ => RGB::gray(c[0] as u16),
+            })
+            .collect();
+        Ok(PPMImg::from_pixels(height, width, maxval as u16, pixels))
+    }
+
+    /// Load the image at `filepath`: a PNG if it ends with `.png`, otherwise a PPM, PGM or PBM
+    pub fn load(filepath: &str) -> io::Result<PPMImg> {
+        if filepath.ends_with(".png") {
+            png::load(filepath)
+        } else {
+            Self::from_reader(&mut BufReader::new(File::open(filepath)?))
+        }
+    }
+}
+
+fn invalid_pnm(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid PPM: {}", msg))
+}
+
+fn not_enough_data() -> io::Error {
+    invalid_pnm(String::from("not enough pixel data"))
+}
+
+/// Reads the whitespace separated parts of a PPM file
+struct PnmParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PnmParser<'a> {
+    /// Skip whitespace and comments, which run from `#` to the end of the line
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.bytes.get(self.pos) {
+            if b == b'#' {
+                while !matches!(self.bytes.get(self.pos), None | Some(b'\n')) {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self, what: &str) -> io::Result<String> {
+        self.skip_space();
+        let start = self.pos;
+        while matches!(self.bytes.get(self.pos), Some(b) if !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(invalid_pnm(format!("expected {}, found end of file", what)));
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+    }
+
+    fn number(&mut self, what: &str) -> io::Result<u32> {
+        let token = self.token(what)?;
+        token
+            .parse()
+            .map_err(|_| invalid_pnm(format!("expected {}, found {:?}", what, token)))
     }
 }
 
@@ -154,7 +407,7 @@ impl Canvas for PPMImg {
     /// Plot a point on this PPMImg at (`x`, `y`, `z`)
     ///
     /// `z` is used for depth-buffer. Will only plot if `z` is closer to screen (new_z > existing_z).
-    fn plot(&mut self, x: i32, y: i32, z: f64) -> () {
+    fn plot(&mut self, x: i32, y: i32, z: f64) {
         // make the origin to be lower left corner
         let y = self.height as i32 - 1 - y;
         if let Some(index) = self.index(x, y) {
@@ -173,38 +426,29 @@ impl Canvas for PPMImg {
     fn get_fg_color(&self) -> RGB {
         self.fg_color
     }
-    fn get_bg_color(&self) -> RGB {
-        self.bg_color
-    }
     fn width(&self) -> u32 {
         self.width
     }
     fn height(&self) -> u32 {
         self.height
     }
+    fn depth(&self) -> u16 {
+        self.depth
+    }
 
+    /// Save in the format given by the extension of `filepath`, which has to be one of `ImageFormat`
     fn save(&self, filepath: &str) -> io::Result<()> {
-        // convert to .png if wanted
-        if filepath.ends_with(".ppm") {
-            self.write_binary(filepath)
-        } else {
-            let mut process = pipe_to_magick(vec!["ppm:-", filepath]);
-
-            // This cmd should have a stdnin, so it's ok to unwrap
-            let mut stdin = process.stdin.take().unwrap();
-            self.write_bin_to_buf(&mut stdin)?;
-
-            drop(stdin);
-            println!("Waiting for convert/magick to exit...");
-            let output = process.wait().expect("Failed to wait on convert/magick");
-            println!("convert/magick {}", output);
-
-            Ok(())
+        match ImageFormat::from_path(filepath) {
+            Some(format) => self.save_as(filepath, format),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported image format, use .ppm, .png or .gif",
+            )),
         }
     }
 
     fn display(&self) {
-        utils::display_ppm(&self);
+        utils::display_ppm(self);
     }
 
     fn clear(&mut self) {
@@ -218,39 +462,104 @@ impl Canvas for PPMImg {
     fn write_to_buf(&self, writer: &mut dyn Write) -> io::Result<()> {
         self.write_bin_to_buf(writer)
     }
+    fn to_ppm(&self) -> PPMImg {
+        self.clone()
+    }
+    fn wraps(&self) -> bool {
+        self.x_wrap || self.y_wrap
+    }
 }
 
-// this will stay here during trait refactor, since it has assumption about the internal data structure for Img
-impl PPMImg {
-    /// Fill an area in img with color calculated by `fill`,
-    /// starting at (x, y) and ending when encounters bound color `bound`.
-    ///
-    /// Note: This function uses the fact that PPMImg is stored as a `Vec` with an `index` method.
-    pub fn bound4_fill_with_fn(
-        &mut self,
-        x: i32,
-        y: i32,
-        fill: impl Fn(f64, f64) -> RGB,
-        bound: RGB,
-    ) {
-        let mut points = vec![(x, y)];
-        while let Some((x, y)) = points.pop() {
-            if let Some(index) = self.index(x, y) {
-                let color = self.data[index];
-                if color == bound {
-                    continue;
-                }
-                let fcolor = fill(x as f64, y as f64);
-                if color == fcolor {
-                    continue;
-                }
-                self.data[index] = fcolor;
-                points.push((x + 1, y));
-                points.push((x, y + 1));
-                points.push((x - 1, y));
-                points.push((x, y - 1));
-            }
-            assert!(points.len() <= (self.width * self.height).try_into().unwrap());
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: &[u8]) -> io::Result<PPMImg> {
+        PPMImg::from_reader(&mut &bytes[..])
+    }
+
+    #[test]
+    fn round_trips() {
+        let pixels = (0..6).map(|i| RGB::new(i * 40, 255 - i, i)).collect();
+        let img = PPMImg::from_pixels(2, 3, 255, pixels);
+        let mut bytes = vec![];
+        img.write_bin_to_buf(&mut bytes).unwrap();
+        assert_eq!(img, read(&bytes).unwrap());
+
+        let deep = PPMImg::from_pixels(1, 2, 65535, vec![RGB::new(65535, 256, 1), RGB::gray(300)]);
+        let mut bytes = vec![];
+        deep.write_bin_to_buf(&mut bytes).unwrap();
+        assert_eq!(deep, read(&bytes).unwrap());
+    }
+
+    #[test]
+    fn ascii_formats() {
+        let img = read(b"P3\n# comment\n2 1 # trailing comment\n15\n15 0 0  0 7 15\n").unwrap();
+        assert_eq!(15, img.depth());
+        assert_eq!(&[RGB::new(15, 0, 0), RGB::new(0, 7, 15)], img.pixels());
+
+        let gray = read(b"P2 2 2 100 0 25 50 100").unwrap();
+        assert_eq!(RGB::gray(50), gray.pixels()[2]);
+
+        // 1 is black in bitmaps
+        let bits = read(b"P1\n3 2\n101\n0 1 0").unwrap();
+        assert_eq!(1, bits.depth());
+        let expected: Vec<RGB> = [0, 1, 0, 1, 0, 1].iter().map(|&v| RGB::gray(v)).collect();
+        assert_eq!(expected, bits.pixels());
+    }
+
+    #[test]
+    fn binary_formats() {
+        let gray = read(b"P5 3 1 255\n\x00\x80\xff").unwrap();
+        assert_eq!(RGB::gray(128), gray.pixels()[1]);
+
+        // rows are padded to whole bytes
+        let bits = read(b"P4\n10 2\n\xff\xc0\x00\x40").unwrap();
+        let pixels = bits.pixels();
+        assert!(pixels[..10].iter().all(|&p| p == RGB::gray(0)));
+        assert_eq!(RGB::gray(0), pixels[19]);
+        assert_eq!(RGB::gray(1), pixels[18]);
+    }
+
+    #[test]
+    fn errors() {
+        let message = |bytes: &[u8]| read(bytes).unwrap_err().to_string();
+        assert_eq!(
+            "invalid PPM: unknown magic number \"P7\"",
+            message(b"P7 1 1 1")
+        );
+        assert_eq!(
+            "invalid PPM: expected height, found end of file",
+            message(b"P3 1")
+        );
+        assert_eq!(
+            "invalid PPM: expected width, found \"x\"",
+            message(b"P3 x 1 255")
+        );
+        assert_eq!(
+            "invalid PPM: pixel value 300 is larger than maxval 255",
+            message(b"P3 1 1 255 0 300 0")
+        );
+        assert_eq!(
+            "invalid PPM: not enough pixel data",
+            message(b"P6 2 1 255\n\x00\x00\x00")
+        );
+        // huge sizes are checked against the data instead of being allocated
+        assert_eq!(
+            "invalid PPM: not enough pixel data",
+            message(b"P6 65535 65535 255\n")
+        );
+        assert_eq!(
+            "invalid PPM: not enough pixel data",
+            message(b"P5 65535 65535 65535\n\x00\x00")
+        );
+        assert_eq!(
+            "invalid PPM: expected pixel value, found end of file",
+            message(b"P3 65535 65535 255 0 0 0")
+        );
+        assert!(read(b"P2 1 1 70000 0").is_err());
+        assert!(read(b"P1 1 1 2").is_err());
+        let err = PPMImg::new(1, 1, 255).save("unsupported.jpg").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
     }
 }
@@ -0,0 +1,247 @@
+//! Phong reflection model used to shade polygons
+//!
+//! I = ambient + diffuse + specular
+//!   = Ia * Ka + Ip * Kd * (N . L) + Ip * Ks * ((2N(N . L) - L) . V)^n
+
+use crate::graphics::{material::Material, vector::Vec3, Matrix, RGB};
+
+/// How polygons are shaded
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ShadingMode {
+    /// One color for each triangle, computed from the surface normal
+    #[default]
+    Flat,
+    /// Colors computed at each vertex, then interpolated across the triangle
+    Gouraud,
+    /// Normals interpolated across the triangle, with colors computed at each pixel
+    Phong,
+}
+
+/// A light source
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Light {
+    /// Light at `pos`, shining in every direction
+    Point { pos: Vec3, color: RGB },
+    /// Light infinitely far away
+    ///
+    /// `dir` points from the surface towards the light
+    Directional { dir: Vec3, color: RGB },
+}
+
+impl Light {
+    pub fn color(&self) -> RGB {
+        match self {
+            Light::Point { color, .. } | Light::Directional { color, .. } => *color,
+        }
+    }
+
+    /// Returns the normalized vector from `point` towards this light
+    pub fn dir_from(&self, point: Vec3) -> Vec3 {
+        match self {
+            Light::Point { pos, .. } => (*pos - point).norm(),
+            Light::Directional { dir, .. } => dir.norm(),
+        }
+    }
+
+    /// Returns this light transformed by `m`
+    ///
+    /// Positions are transformed as points and directions as vectors, so translations don't move directional lights.
+    pub fn transformed(&self, m: &Matrix) -> Light {
+        let apply = |v: Vec3, w: f64| {
+            let r = Matrix::new(1, 4, vec![v.0, v.1, v.2, w])._mul(m);
+            Vec3(
+                r.get(0, 0).unwrap(),
+                r.get(0, 1).unwrap(),
+                r.get(0, 2).unwrap(),
+            )
+        };
+        match *self {
+            Light::Point { pos, color } => Light::Point {
+                pos: apply(pos, 1.),
+                color,
+            },
+            Light::Directional { dir, color } => Light::Directional {
+                dir: apply(dir, 0.),
+                color,
+            },
+        }
+    }
+}
+
+/// Everything needed to compute the color of a surface
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightConfig {
+    /// Vector pointing from the surface towards the viewer
+    pub view: Vec3,
+    pub ambient: RGB,
+    /// Named light sources, in the order they are added
+    pub lights: Vec<(String, Light)>,
+}
+
+impl Default for LightConfig {
+    fn default() -> Self {
+        LightConfig {
+            view: Vec3(0., 0., 1.),
+            ambient: RGB::gray(50),
+            lights: vec![(
+                String::from("default"),
+                Light::Directional {
+                    dir: Vec3(0.5, 0.75, 1.),
+                    color: RGB::gray(255),
+                },
+            )],
+        }
+    }
+}
+
+impl LightConfig {
+    /// Add a light named `name`, replacing any light that already has that name
+    pub fn add_light(&mut self, name: &str, light: Light) {
+        match self.lights.iter_mut().find(|(n, _)| n == name) {
+            Some((_, l)) => *l = light,
+            None => self.lights.push((name.to_string(), light)),
+        }
+    }
+
+    /// Computes the color at `point` of a surface made of `k` with `normal` (does not need to be normalized)
+    ///
+    /// Contributions of all lights are summed, then clamped to [0, `max`], the depth of the canvas.
+    pub fn get_color(&self, normal: Vec3, point: Vec3, k: &Material, max: u16) -> RGB {
+        let n = normal.norm();
+        let v = self.view.norm();
+
+        // start with emissive and ambient, then add diffuse and specular of every light
+        let a = self.ambient;
+        let e = k.emissive.unwrap_or_else(|| RGB::gray(0));
+        let mut i = (
+            e.red as f64 + a.red as f64 * k.ambient.0,
+            e.green as f64 + a.green as f64 * k.ambient.1,
+            e.blue as f64 + a.blue as f64 * k.ambient.2,
+        );
+
+        for (_, light) in self.lights.iter() {
+            let l = light.dir_from(point);
+            let n_dot_l = n.dot(l);
+            if n_dot_l <= 0. {
+                // light is behind the surface
+                continue;
+            }
+            // reflection of l over n
+            let r = n * (2. * n_dot_l) - l;
+            let specular = r.dot(v).max(0.).powf(k.shininess);
+
+            let p = light.color();
+            i.0 += p.red as f64 * (k.diffuse.0 * n_dot_l + k.specular.0 * specular);
+            i.1 += p.green as f64 * (k.diffuse.1 * n_dot_l + k.specular.1 * specular);
+            i.2 += p.blue as f64 * (k.diffuse.2 * n_dot_l + k.specular.2 * specular);
+        }
+
+        let clamp = |i: f64| i.clamp(0., max as f64).round() as u16;
+        RGB::new(clamp(i.0), clamp(i.1), clamp(i.2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::transform as tr;
+
+    fn single_light(light: Light) -> LightConfig {
+        LightConfig {
+            lights: vec![(String::from("light"), light)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn facing_away_from_light_is_ambient_only() {
+        let k = Material::default();
+        let config = single_light(Light::Directional {
+            dir: Vec3(0., 0., 1.),
+            color: RGB::gray(255),
+        });
+        // ambient: 50 * 0.1
+        assert_eq!(
+            RGB::gray(5),
+            config.get_color(Vec3(0., 0., -1.), Vec3(0., 0., 0.), &k, 255)
+        );
+    }
+
+    #[test]
+    fn facing_light_and_viewer_is_brightest() {
+        let k = Material::default();
+        let config = single_light(Light::Directional {
+            dir: Vec3(0., 0., 1.),
+            color: RGB::gray(255),
+        });
+        // 5 + 255 * 0.5 + 255 * 0.5, clamped
+        let origin = Vec3(0., 0., 0.);
+        assert_eq!(
+            RGB::gray(255),
+            config.get_color(Vec3(0., 0., 20.), origin, &k, 255)
+        );
+        assert!(config.get_color(Vec3(1., 0., 1.), origin, &k, 255).red < 255);
+
+        // deeper canvases have room for more
+        assert_eq!(
+            RGB::gray(260),
+            config.get_color(Vec3(0., 0., 20.), origin, &k, 1023)
+        );
+    }
+
+    #[test]
+    fn colored_lights_sum() {
+        let k = Material::default();
+        let mut config = single_light(Light::Point {
+            pos: Vec3(0., 0., 100.),
+            color: RGB::new(100, 0, 0),
+        });
+        config.add_light(
+            "blue",
+            Light::Directional {
+                dir: Vec3(0., 0., 1.),
+                color: RGB::new(0, 0, 100),
+            },
+        );
+        let color = config.get_color(Vec3(0., 0., 1.), Vec3(0., 0., 0.), &k, 255);
+        // 5 + 100 * 0.5 + 100 * 0.5 on lit channels
+        assert_eq!(RGB::new(105, 5, 105), color);
+    }
+
+    #[test]
+    fn emissive_is_added() {
+        let config = single_light(Light::Directional {
+            dir: Vec3(0., 0., 1.),
+            color: RGB::gray(255),
+        });
+        let k = Material {
+            emissive: Some(RGB::new(0, 100, 0)),
+            ..Default::default()
+        };
+        assert_eq!(
+            RGB::new(5, 105, 5),
+            config.get_color(Vec3(0., 0., -1.), Vec3(0., 0., 0.), &k, 255)
+        );
+    }
+
+    #[test]
+    fn transform_light() {
+        let m = tr::mv(10., 0., 0.);
+        let point = Light::Point {
+            pos: Vec3(0., 0., 0.),
+            color: RGB::gray(255),
+        };
+        let dir = Light::Directional {
+            dir: Vec3(0., 0., 1.),
+            color: RGB::gray(255),
+        };
+        assert_eq!(
+            Light::Point {
+                pos: Vec3(10., 0., 0.),
+                color: RGB::gray(255)
+            },
+            point.transformed(&m)
+        );
+        assert_eq!(dir, dir.transformed(&m));
+    }
+}
@@ -0,0 +1,72 @@
+//! Surface properties used by the lighting model
+
+use crate::graphics::RGB;
+
+/// Reflection constants of a surface, one value per color channel (red, green, blue)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    pub ambient: (f64, f64, f64),
+    pub diffuse: (f64, f64, f64),
+    pub specular: (f64, f64, f64),
+    /// Exponent for specular highlights; the larger it is, the smaller the highlight
+    pub shininess: f64,
+    /// Color given off by the surface regardless of lights
+    pub emissive: Option<RGB>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            ambient: (0.1, 0.1, 0.1),
+            diffuse: (0.5, 0.5, 0.5),
+            specular: (0.5, 0.5, 0.5),
+            shininess: 8.,
+            emissive: None,
+        }
+    }
+}
+
+impl Material {
+    /// A material that looks like `color` under white light
+    pub fn with_color(color: RGB) -> Self {
+        let (r, g, b) = (
+            color.red as f64 / 255.,
+            color.green as f64 / 255.,
+            color.blue as f64 / 255.,
+        );
+        Material {
+            ambient: (r * 0.2, g * 0.2, b * 0.2),
+            diffuse: (r * 0.8, g * 0.8, b * 0.8),
+            ..Default::default()
+        }
+    }
+
+    /// The color the material looks like under white light shining straight at it, without highlights
+    pub fn base_color(&self) -> RGB {
+        let channel = |ambient: f64, diffuse: f64| {
+            ((ambient + diffuse).min(1.) * 255.).round() as u16
+        };
+        RGB::new(
+            channel(self.ambient.0, self.diffuse.0),
+            channel(self.ambient.1, self.diffuse.1),
+            channel(self.ambient.2, self.diffuse.2),
+        )
+    }
+
+    /// A material with no specular highlights
+    pub fn matte(self) -> Self {
+        Material {
+            specular: (0., 0., 0.),
+            ..self
+        }
+    }
+
+    /// A material with strong, small specular highlights
+    pub fn shiny(self) -> Self {
+        Material {
+            specular: (0.8, 0.8, 0.8),
+            shininess: 32.,
+            ..self
+        }
+    }
+}
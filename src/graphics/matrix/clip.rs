@@ -0,0 +1,189 @@
+//! Clipping edges and polygons against planes in homogeneous coordinates
+//!
+//! A plane is `[a, b, c, d]`, and point (x, y, z, w) is inside when `a*x + b*y + c*z + d*w >= 0`.
+//! Edges are clipped with Liang–Barsky, triangles with Sutherland–Hodgman and then fanned back into triangles.
+
+use super::Matrix;
+
+pub type Plane = [f64; 4];
+
+/// The view frustum in clip space: -w <= x, y, z <= w
+#[rustfmt::skip]
+pub const FRUSTUM: [Plane; 6] = [
+    [ 1.,  0.,  0., 1.],
+    [-1.,  0.,  0., 1.],
+    [ 0.,  1.,  0., 1.],
+    [ 0., -1.,  0., 1.],
+    [ 0.,  0.,  1., 1.],
+    [ 0.,  0., -1., 1.],
+];
+
+/// The pixels of a `width` by `height` canvas in screen coordinates, z isn't clipped
+#[rustfmt::skip]
+pub fn canvas_planes(width: f64, height: f64) -> [Plane; 4] {
+    [
+        [ 1.,  0., 0., 0.],
+        [-1.,  0., 0., width - 1.],
+        [ 0.,  1., 0., 0.],
+        [ 0., -1., 0., height - 1.],
+    ]
+}
+
+fn distance(plane: &Plane, point: &[f64]) -> f64 {
+    plane.iter().zip(point).map(|(a, b)| a * b).sum()
+}
+
+fn lerp(p0: &[f64], p1: &[f64], t: f64) -> Vec<f64> {
+    p0.iter().zip(p1).map(|(a, b)| a + (b - a) * t).collect()
+}
+
+impl Matrix {
+    /// Returns this edge matrix with each edge cut down to the part inside all `planes`
+    ///
+    /// Edges entirely outside are removed.
+    pub fn clip_edges(&self, planes: &[Plane]) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for edge in self.data.chunks(self.ncols * 2) {
+            let (p0, p1) = edge.split_at(self.ncols);
+            let (mut t0, mut t1) = (0f64, 1f64);
+            let mut inside = true;
+            for plane in planes {
+                let (d0, d1) = (distance(plane, p0), distance(plane, p1));
+                if d0 < 0. && d1 < 0. {
+                    inside = false;
+                    break;
+                }
+                if d0 < 0. {
+                    t0 = t0.max(d0 / (d0 - d1));
+                } else if d1 < 0. {
+                    t1 = t1.min(d0 / (d0 - d1));
+                }
+            }
+            if !inside || t0 > t1 {
+                continue;
+            }
+            if t0 == 0. && t1 == 1. {
+                data.extend_from_slice(edge);
+            } else {
+                data.extend(lerp(p0, p1, t0));
+                data.extend(lerp(p0, p1, t1));
+            }
+        }
+        Matrix::new(data.len() / self.ncols, self.ncols, data)
+    }
+
+    /// Returns this polygon matrix with each triangle cut down to the part inside all `planes`
+    ///
+    /// Triangles partly inside are cut into a polygon that is split back into triangles with the same winding.
+    /// Triangles entirely outside are removed.
+    pub fn clip_polygons(&self, planes: &[Plane]) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for triangle in self.data.chunks(self.ncols * 3) {
+            let points: Vec<&[f64]> = triangle.chunks(self.ncols).collect();
+            if planes
+                .iter()
+                .all(|plane| points.iter().all(|p| distance(plane, p) >= 0.))
+            {
+                data.extend_from_slice(triangle);
+                continue;
+            }
+
+            let mut polygon: Vec<Vec<f64>> = points.iter().map(|p| p.to_vec()).collect();
+            for plane in planes {
+                if polygon.is_empty() {
+                    break;
+                }
+                let mut clipped = Vec::with_capacity(polygon.len() + 1);
+                let mut prev = polygon.last().unwrap();
+                let mut d_prev = distance(plane, prev);
+                for cur in polygon.iter() {
+                    let d_cur = distance(plane, cur);
+                    if (d_cur >= 0.) != (d_prev >= 0.) {
+                        clipped.push(lerp(prev, cur, d_prev / (d_prev - d_cur)));
+                    }
+                    if d_cur >= 0. {
+                        clipped.push(cur.clone());
+                    }
+                    prev = cur;
+                    d_prev = d_cur;
+                }
+                polygon = clipped;
+            }
+
+            // fan out from the first point
+            for i in 2..polygon.len() {
+                data.extend_from_slice(&polygon[0]);
+                data.extend_from_slice(&polygon[i - 1]);
+                data.extend_from_slice(&polygon[i]);
+            }
+        }
+        Matrix::new(data.len() / self.ncols, self.ncols, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(m: &Matrix) -> Vec<Vec<f64>> {
+        m.iter_by_row().map(|r| r.to_vec()).collect()
+    }
+
+    #[test]
+    fn edges() {
+        let planes = canvas_planes(11., 11.);
+        let mut m = Matrix::new_edge_matrix();
+        m.append_edge(&[2., 2., 0., 8., 8., 0.]);
+        m.append_edge(&[-10., 5., 0., 20., 5., 6.]);
+        m.append_edge(&[-10., -1., 0., 20., -1., 0.]);
+        m.append_edge(&[20., 0., 0., 0., 20., 0.]);
+        assert_eq!(
+            vec![
+                vec![2., 2., 0., 1.],
+                vec![8., 8., 0., 1.],
+                vec![0., 5., 2., 1.],
+                vec![10., 5., 4., 1.],
+                vec![10., 10., 0., 1.],
+                vec![10., 10., 0., 1.],
+            ],
+            rows(&m.clip_edges(&planes))
+        );
+    }
+
+    #[test]
+    fn homogeneous_edges() {
+        // from in front of the eye to behind it, where w < 0
+        let m = Matrix::new(2, 4, vec![0., 0., 0., 2., 0., 0., 4., -2.]);
+        let clipped = rows(&m.clip_edges(&FRUSTUM));
+        assert_eq!(vec![0., 0., 0., 2.], clipped[0]);
+        assert_eq!(vec![0., 0., 1., 1.], clipped[1]);
+    }
+
+    #[test]
+    fn polygons() {
+        let planes = canvas_planes(11., 11.);
+        let mut m = Matrix::new_polygon_matrix();
+        // inside, untouched
+        m.append_polygon((1., 1., 0.), (5., 1., 0.), (1., 5., 0.));
+        // outside
+        m.append_polygon((20., 1., 0.), (25., 1., 0.), (20., 5., 0.));
+        // one corner cut off, leaving a quad
+        m.append_polygon((0., 0., 0.), (20., 0., 0.), (0., 10., 0.));
+        let clipped = m.clip_polygons(&planes);
+        let rows = rows(&clipped);
+        assert_eq!(3 + 6, rows.len());
+        assert_eq!(vec![1., 1., 0., 1.], rows[0]);
+        for p in rows.iter() {
+            assert!((0. ..=10.).contains(&p[0]) && (0. ..=10.).contains(&p[1]));
+        }
+
+        // winding is kept, so the normals still face the same way
+        for t in rows[3..].chunks(3) {
+            let (a, b) = (
+                (t[1][0] - t[0][0], t[1][1] - t[0][1]),
+                (t[2][0] - t[0][0], t[2][1] - t[0][1]),
+            );
+            assert!(a.0 * b.1 - a.1 * b.0 > 0.);
+        }
+    }
+}
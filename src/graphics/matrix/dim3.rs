@@ -1,7 +1,8 @@
 //! Implements fn that add shapes to a vertex matrix
 
 use super::Matrix;
-use std::f64::consts::PI;
+use crate::graphics::vector::Vec3;
+use std::{collections::HashMap, f64::consts::PI};
 
 // constructor
 impl Matrix {
@@ -28,6 +29,55 @@ impl Matrix {
     }
 }
 
+// normals
+impl Matrix {
+    /// Returns the normal of every triangle in this polygon matrix, not normalized
+    ///
+    /// Normals point towards the side the triangle's vertices go counter-clockwise around,
+    /// which is the side that's drawn.
+    pub fn surface_normals(&self) -> Vec<Vec3> {
+        self.data
+            .chunks_exact(self.ncols * 3)
+            .map(|triangle| {
+                let v = |i: usize| {
+                    let row = &triangle[i * self.ncols..];
+                    Vec3(row[0], row[1], row[2])
+                };
+                (v(1) - v(0)).cross(v(2) - v(0))
+            })
+            .collect()
+    }
+
+    /// Returns the normal of every vertex in this polygon matrix, in the same order as the rows.
+    ///
+    /// A vertex normal is the average of the normals of all triangles sharing that vertex,
+    /// where vertices are shared if they are at the same position.
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        // vertices that are this close are considered the same
+        let key = |row: &[f64]| {
+            let q = |x: f64| (x * 1e6).round() as i64;
+            (q(row[0]), q(row[1]), q(row[2]))
+        };
+
+        let mut sums: HashMap<(i64, i64, i64), Vec3> = HashMap::new();
+        let triangles = self.data.chunks_exact(self.ncols * 3);
+        for (triangle, normal) in triangles.zip(self.surface_normals()) {
+            let (r0, r1, r2) = (
+                &triangle[..self.ncols],
+                &triangle[self.ncols..self.ncols * 2],
+                &triangle[self.ncols * 2..],
+            );
+            let normal = normal.norm();
+            for row in &[r0, r1, r2] {
+                let sum = sums.entry(key(row)).or_insert(Vec3(0., 0., 0.));
+                *sum = *sum + normal;
+            }
+        }
+
+        self.iter_by_row().map(|row| sums[&key(row)].norm()).collect()
+    }
+}
+
 // box, sphere, torus
 impl Matrix {
     /// Add a 3d rectangular box to the matrix
@@ -89,6 +139,11 @@ impl Matrix {
     }
 
     pub fn add_sphere(&mut self, center: (f64, f64, f64), radius: f64) {
+        self.add_ellipsoid(center, radius, radius, radius);
+    }
+
+    /// Add an ellipsoid with radius `rx` along the x axis, `ry` along y and `rz` along z
+    pub fn add_ellipsoid(&mut self, center: (f64, f64, f64), rx: f64, ry: f64, rz: f64) {
         let steps = 24;
 
         let (cx, cy, cz) = center;
@@ -107,9 +162,9 @@ impl Matrix {
             let rotc = rot as f64 * 2. * PI / steps as f64;
             for cir in 0..=steps {
                 let circ = cir as f64 * PI / steps as f64;
-                let x = radius * circ.cos() + cx;
-                let y = radius * circ.sin() * rotc.cos() + cy;
-                let z = radius * circ.sin() * rotc.sin() + cz;
+                let x = rx * circ.cos() + cx;
+                let y = ry * circ.sin() * rotc.cos() + cy;
+                let z = rz * circ.sin() * rotc.sin() + cz;
                 points.push((x, y, z));
             }
         }
@@ -194,23 +249,206 @@ impl Matrix {
     }
 }
 
+// cylinder, cone, pyramid
+//
+// These stand on the xz plane: `base` is the center of their bottom and they go up along y.
+impl Matrix {
+    /// Add a cylinder with both ends capped
+    pub fn add_cylinder(&mut self, base: (f64, f64, f64), radius: f64, height: f64) {
+        self.add_frustum(base, radius, radius, height);
+    }
+
+    /// Add a cone with its tip `height` above the center of its base
+    pub fn add_cone(&mut self, base: (f64, f64, f64), radius: f64, height: f64) {
+        self.add_frustum(base, radius, 0., height);
+    }
+
+    /// Add a cone cut flat at the top, `radius_bottom` wide at the base and `radius_top` at the top
+    ///
+    /// Ends with a radius of 0 come to a point instead of being capped.
+    pub fn add_frustum(
+        &mut self,
+        (x, y, z): (f64, f64, f64),
+        radius_bottom: f64,
+        radius_top: f64,
+        height: f64,
+    ) {
+        let steps = 24;
+
+        // counter-clockwise seen from above
+        let ring = |radius: f64, y: f64| -> Vec<(f64, f64, f64)> {
+            (0..=steps)
+                .map(|i| {
+                    let angle = i as f64 * 2. * PI / steps as f64;
+                    (x + radius * angle.cos(), y, z - radius * angle.sin())
+                })
+                .collect()
+        };
+        let (bottom, top) = (ring(radius_bottom, y), ring(radius_top, y + height));
+        let (bottom_center, top_center) = ((x, y, z), (x, y + height, z));
+
+        for i in 0..steps {
+            // sides, leaving out the triangles that would have no area at a tip
+            if radius_bottom != 0. {
+                self.append_polygon(bottom[i], bottom[i + 1], top[i]);
+                self.append_polygon(bottom_center, bottom[i + 1], bottom[i]);
+            }
+            if radius_top != 0. {
+                self.append_polygon(bottom[i + 1], top[i + 1], top[i]);
+                self.append_polygon(top_center, top[i], top[i + 1]);
+            }
+        }
+    }
+
+    /// Add a pyramid with a square base `side` wide, its tip `height` above the center of the base
+    pub fn add_pyramid(&mut self, (x, y, z): (f64, f64, f64), side: f64, height: f64) {
+        let half = side / 2.;
+        let front_left = (x - half, y, z + half);
+        let front_right = (x + half, y, z + half);
+        let back_right = (x + half, y, z - half);
+        let back_left = (x - half, y, z - half);
+        let tip = (x, y + height, z);
+
+        self.append_polygon(front_left, front_right, tip);
+        self.append_polygon(front_right, back_right, tip);
+        self.append_polygon(back_right, back_left, tip);
+        self.append_polygon(back_left, front_left, tip);
+
+        // btm
+        self.append_polygon(front_left, back_left, back_right);
+        self.append_polygon(front_left, back_right, front_right);
+    }
+}
+
+/// Regular polyhedra with triangular faces
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Polyhedron {
+    Tetrahedron,
+    Octahedron,
+    Icosahedron,
+}
+
+impl Polyhedron {
+    /// Vertices of the polyhedron centered at the origin, at a distance of 1 from it
+    fn vertices(self) -> Vec<Vec3> {
+        let vertices = match self {
+            Polyhedron::Tetrahedron => vec![
+                Vec3(1., 1., 1.),
+                Vec3(1., -1., -1.),
+                Vec3(-1., 1., -1.),
+                Vec3(-1., -1., 1.),
+            ],
+            Polyhedron::Octahedron => vec![
+                Vec3(1., 0., 0.),
+                Vec3(-1., 0., 0.),
+                Vec3(0., 1., 0.),
+                Vec3(0., -1., 0.),
+                Vec3(0., 0., 1.),
+                Vec3(0., 0., -1.),
+            ],
+            Polyhedron::Icosahedron => {
+                // golden ratio
+                let phi = (1. + 5f64.sqrt()) / 2.;
+                let mut vertices = Vec::with_capacity(12);
+                for &a in [1., -1.].iter() {
+                    for &b in [phi, -phi].iter() {
+                        vertices.push(Vec3(0., a, b));
+                        vertices.push(Vec3(a, b, 0.));
+                        vertices.push(Vec3(b, 0., a));
+                    }
+                }
+                vertices
+            }
+        };
+        vertices.into_iter().map(|v| v.norm()).collect()
+    }
+}
+
+// polyhedra, plane
+impl Matrix {
+    /// Add `polyhedron` with its vertices `radius` away from `center`
+    pub fn add_polyhedron(
+        &mut self,
+        polyhedron: Polyhedron,
+        (x, y, z): (f64, f64, f64),
+        radius: f64,
+    ) {
+        let vertices = polyhedron.vertices();
+        // faces are the triangles made of 3 vertices that are all one edge apart,
+        // which is the shortest distance between two vertices
+        let dist = |a: usize, b: usize| (vertices[a] - vertices[b]).mag();
+        let edge = (1..vertices.len()).map(|i| dist(0, i)).fold(f64::MAX, f64::min);
+        let is_edge = |a: usize, b: usize| (dist(a, b) - edge).abs() < 1e-9;
+
+        let point = |v: Vec3| (x + v.0 * radius, y + v.1 * radius, z + v.2 * radius);
+        let n = vertices.len();
+        for a in 0..n {
+            for b in a + 1..n {
+                for c in b + 1..n {
+                    if !(is_edge(a, b) && is_edge(b, c) && is_edge(a, c)) {
+                        continue;
+                    }
+                    let (va, vb, vc) = (vertices[a], vertices[b], vertices[c]);
+                    // wind the face counter-clockwise seen from outside
+                    let normal = (vb - va).cross(vc - va);
+                    if normal.dot(va + vb + vc) > 0. {
+                        self.append_polygon(point(va), point(vb), point(vc));
+                    } else {
+                        self.append_polygon(point(va), point(vc), point(vb));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add a flat rectangle facing +z, split into `columns` by `rows` squares of 2 triangles each
+    ///
+    /// Like the front of a box, `corner` is the upper left corner and the plane goes down `height`.
+    /// More squares light more smoothly with point lights and Gouraud or Phong shading.
+    pub fn add_plane(
+        &mut self,
+        (x, y, z): (f64, f64, f64),
+        width: f64,
+        height: f64,
+        columns: usize,
+        rows: usize,
+    ) {
+        let point = |column: usize, row: usize| {
+            (
+                x + width * column as f64 / columns as f64,
+                y - height * row as f64 / rows as f64,
+                z,
+            )
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                let upper_left = point(column, row);
+                let lower_left = point(column, row + 1);
+                let upper_right = point(column + 1, row);
+                let lower_right = point(column + 1, row + 1);
+                self.append_polygon(upper_left, lower_left, upper_right);
+                self.append_polygon(upper_right, lower_left, lower_right);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::graphics::{matrix::transform, utils::display_polygon_matrix};
+    use crate::graphics::{golden::assert_polygon_matrix, matrix::transform};
 
     #[test]
     fn draw_sphere() {
         let mut m = Matrix::new_polygon_matrix();
         m.add_sphere((250., 250., 0.), 400.);
         m *= transform::rotatex(40.) * transform::rotatey(90.);
-        println!("len of array: {}", m.data.len());
-        display_polygon_matrix(&m, false);
+        assert_polygon_matrix("sphere", &m);
     }
 
     #[test]
     fn test_no_degenerate_triangles_in_sphere() {
-        for radius in (0..1000).into_iter().step_by(100) {
+        for radius in (0..1000).step_by(100) {
             let mut m = Matrix::new_polygon_matrix();
             m.add_sphere((0., 0., 0.), radius as f64 + 0.3);
 
@@ -226,12 +464,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sphere_vertex_normals_point_outwards() {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_sphere((10., 20., 30.), 50.);
+        let normals = m.vertex_normals();
+        assert_eq!(m.nrows, normals.len());
+        for (row, normal) in m.iter_by_row().zip(normals) {
+            let outwards = (Vec3(row[0], row[1], row[2]) - Vec3(10., 20., 30.)).norm();
+            assert!(normal.dot(outwards) > 0.99, "{:?} vs {:?}", normal, outwards);
+        }
+    }
+
     #[test]
     fn draw_torus() {
         let mut m = Matrix::new_edge_matrix();
         m.add_torus((250., 250., 0.), 30., 100.);
         m *= transform::rotatex(40.);
-        display_polygon_matrix(&m, false);
+        assert_polygon_matrix("torus", &m);
     }
     #[test]
     fn draw_cube() {
@@ -243,6 +493,102 @@ mod tests {
             * transform::rotatex(40.)
             * transform::rotatey(20.);
 
-        display_polygon_matrix(&m, false);
+        assert_polygon_matrix("cube", &m);
+    }
+
+    /// Asserts every triangle of `m` has an area and faces away from `inside(centroid)`
+    fn assert_faces_outwards(name: &str, m: &Matrix, inside: impl Fn(Vec3) -> Vec3) {
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        for (triangle, normal) in rows.chunks(3).zip(m.surface_normals()) {
+            let v = |i: usize| Vec3(triangle[i][0], triangle[i][1], triangle[i][2]);
+            let centroid = (v(0) + v(1) + v(2)) / 3.;
+            assert!(normal.mag() > 1e-9, "{}: triangle without area", name);
+            let outwards = centroid - inside(centroid);
+            assert!(normal.dot(outwards) > 0., "{}: {:?} faces inwards", name, triangle);
+        }
+    }
+
+    #[test]
+    fn solids_face_outwards() {
+        let center = Vec3(10., 20., 30.);
+        let c = (10., 20., 30.);
+        // all the solids are convex, so any point inside will do
+        let above = |dy: f64| move |_| center + Vec3(0., dy, 0.);
+
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_cylinder(c, 5., 10.);
+        assert_eq!(24 * 4, m.nrows / 3);
+        assert_faces_outwards("cylinder", &m, above(5.));
+
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_cone(c, 5., 10.);
+        assert_eq!(24 * 2, m.nrows / 3);
+        assert_faces_outwards("cone", &m, above(2.));
+
+        // upside down
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_frustum(c, 0., 5., 10.);
+        assert_faces_outwards("frustum", &m, above(8.));
+
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_pyramid(c, 6., 8.);
+        assert_eq!(6, m.nrows / 3);
+        assert_faces_outwards("pyramid", &m, above(2.));
+
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_ellipsoid(c, 5., 10., 20.);
+        for row in m.iter_by_row() {
+            let (x, y, z) = ((row[0] - c.0) / 5., (row[1] - c.1) / 10., (row[2] - c.2) / 20.);
+            assert!((x * x + y * y + z * z - 1.).abs() < 1e-9, "{:?}", row);
+        }
+
+        for &(polyhedron, faces) in [
+            (Polyhedron::Tetrahedron, 4),
+            (Polyhedron::Octahedron, 8),
+            (Polyhedron::Icosahedron, 20),
+        ]
+        .iter()
+        {
+            let mut m = Matrix::new_polygon_matrix();
+            m.add_polyhedron(polyhedron, c, 7.);
+            assert_eq!(faces, m.nrows / 3, "{:?}", polyhedron);
+            for row in m.iter_by_row() {
+                let distance = (Vec3(row[0], row[1], row[2]) - center).mag();
+                assert!((distance - 7.).abs() < 1e-9, "{:?}", polyhedron);
+            }
+            assert_faces_outwards(&format!("{:?}", polyhedron), &m, above(0.));
+        }
+    }
+
+    #[test]
+    fn plane() {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_plane((0., 10., 5.), 30., 10., 3, 2);
+        assert_eq!(3 * 2 * 2, m.nrows / 3);
+        for normal in m.surface_normals() {
+            assert_eq!(Vec3(0., 0., 50.), normal);
+        }
+        let range = |axis: usize| {
+            let values = m.iter_by_row().map(|row| row[axis]);
+            let min = values.clone().fold(f64::MAX, f64::min);
+            (min, values.fold(f64::MIN, f64::max))
+        };
+        assert_eq!((0., 30.), range(0));
+        assert_eq!((0., 10.), range(1));
+    }
+
+    #[test]
+    fn draw_solids() {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_cylinder((100., 320., 0.), 50., 100.);
+        m.add_cone((250., 320., 0.), 50., 100.);
+        m.add_pyramid((400., 320., 0.), 100., 100.);
+        m.add_ellipsoid((100., 150., 0.), 60., 40., 30.);
+        m.add_polyhedron(Polyhedron::Tetrahedron, (250., 150., 0.), 60.);
+        m.add_polyhedron(Polyhedron::Icosahedron, (400., 150., 0.), 60.);
+        m *= transform::mv(-250., -250., 0.) * transform::rotatex(25.) * transform::rotatey(20.)
+            * transform::mv(250., 250., 0.);
+        m.add_plane((50., 60., -100.), 400., 40., 8, 2);
+        assert_polygon_matrix("solids", &m);
     }
 }
@@ -11,9 +11,12 @@ pub mod projections;
 pub mod transform;
 
 // impl on Matrix
+pub mod clip;
 pub mod dim2;
 pub mod dim3;
+pub mod obj;
 pub mod parametrics;
+pub mod stl;
 // pub mod mstack;
 
 #[derive(Clone, Debug)]
@@ -33,7 +36,7 @@ impl Matrix {
         // col * self.nrows + row
     }
 
-    pub fn new_clone_vec(nrows: usize, ncols: usize, data: &Vec<f64>) -> Matrix {
+    pub fn new_clone_vec(nrows: usize, ncols: usize, data: &[f64]) -> Matrix {
         assert_eq!(
             nrows * ncols,
             data.len(),
@@ -43,7 +46,7 @@ impl Matrix {
         Matrix {
             nrows,
             ncols,
-            data: data.clone(),
+            data: data.to_vec(),
         }
     }
 
@@ -74,6 +77,14 @@ impl Matrix {
         self.nrows = 0;
         self.data.clear();
     }
+
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
 }
 
 // add row
@@ -92,13 +103,13 @@ impl Matrix {
 // row and col iter
 impl Matrix {
     /// Iterate over a certain row
-    pub fn row_iter<'a>(&'a self, r: usize) -> impl Iterator<Item = &f64> {
+    pub fn row_iter(&self, r: usize) -> impl Iterator<Item = &f64> {
         let start = r * self.ncols;
         self.data[start..start + self.ncols].iter()
     }
 
     /// Iterate over a certain column
-    pub fn col_iter<'a>(&'a self, c: usize) -> impl Iterator<Item = &f64> {
+    pub fn col_iter(&self, c: usize) -> impl Iterator<Item = &f64> {
         self.data.iter().skip(c).step_by(self.ncols)
     }
 
@@ -194,7 +205,7 @@ impl MulAssign for Matrix {
 
 impl MulAssign<&Matrix> for Matrix {
     fn mul_assign(&mut self, rhs: &Matrix) {
-        *self = self._mul(&rhs)
+        *self = self._mul(rhs)
     }
 }
 
@@ -210,17 +221,12 @@ impl Matrix {
     }
 
     /// Transforms self into an identity matrix
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_ident(&mut self) {
         let ncols = self.ncols;
         for (i, d) in self.data.iter_mut().enumerate() {
-            *d = if {
-                let (r, c) = Matrix::index_to_rc(i, ncols);
-                r == c
-            } {
-                1.0
-            } else {
-                0.0
-            }
+            let (r, c) = Matrix::index_to_rc(i, ncols);
+            *d = if r == c { 1.0 } else { 0.0 }
         }
     }
 }
@@ -277,8 +283,7 @@ mod tests {
         let mut m = Matrix::new(0, 4, vec![]);
         println!("m: {}", m);
         println!("Adding (1, 2, 4) and (5, 6, 7) to empty matrix",);
-        m.append_edge(&mut vec![1.0, 2.0, 4.0]);
-        m.append_edge(&mut vec![5.0, 6.0, 7.0]);
+        m.append_edge(&[1.0, 2.0, 4.0, 5.0, 6.0, 7.0]);
         println!("m: {}", m);
         assert!(
             matrix_equal(
@@ -0,0 +1,409 @@
+//! Loading Wavefront OBJ meshes into polygon matrices
+//!
+//! Reads vertices (`v`), texture coordinates (`vt`), normals (`vn`), faces (`f`)
+//! and groups (`o` and `g`). Everything else, like materials and smoothing groups, is skipped.
+//!
+//! Faces can have any number of vertices, and are split into triangles keeping their winding,
+//! so faces that are counter-clockwise in the file stay counter-clockwise.
+//! Indices count from 1, or from the end of the list when negative, as in the OBJ format.
+
+use super::Matrix;
+use crate::graphics::vector::Vec3;
+use std::{fs, io};
+
+/// A corner of a face, with indices counting from 0
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FaceVertex {
+    pub vertex: usize,
+    pub tex_coord: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+/// Faces under the same `o` or `g` name
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    /// Empty for faces before the first group
+    pub name: String,
+    pub faces: Vec<Vec<FaceVertex>>,
+}
+
+/// The contents of an OBJ file
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Obj {
+    pub vertices: Vec<(f64, f64, f64)>,
+    pub tex_coords: Vec<(f64, f64)>,
+    pub normals: Vec<Vec3>,
+    /// Groups in the order they first appear, without empty ones
+    pub groups: Vec<Group>,
+}
+
+fn invalid(line: usize, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
+
+/// Parse `n` numbers from `args`, ignoring any extra ones, like the `w` of `v x y z w`
+fn numbers(line: usize, args: &[&str], n: usize) -> io::Result<Vec<f64>> {
+    if args.len() < n {
+        return Err(invalid(
+            line,
+            format!("expected {} numbers, found {}", n, args.len()),
+        ));
+    }
+    args[..n]
+        .iter()
+        .map(|a| {
+            a.parse()
+                .map_err(|_| invalid(line, format!("expected a number, found `{}`", a)))
+        })
+        .collect()
+}
+
+/// Resolve index `s` into a list of `len` items
+fn index(line: usize, s: &str, len: usize, what: &str) -> io::Result<usize> {
+    let i: i64 = s
+        .parse()
+        .map_err(|_| invalid(line, format!("expected a {} index, found `{}`", what, s)))?;
+    let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(invalid(
+            line,
+            format!("{} index {} is out of range, there are {}", what, i, len),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+impl Obj {
+    pub fn load(filepath: &str) -> io::Result<Obj> {
+        Obj::parse(&fs::read_to_string(filepath)?)
+    }
+
+    pub fn parse(src: &str) -> io::Result<Obj> {
+        let mut obj = Obj::default();
+        let mut group = Group {
+            name: String::new(),
+            faces: Vec::new(),
+        };
+
+        for (i, line) in src.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            let args: Vec<&str> = words.collect();
+
+            match keyword {
+                "v" => {
+                    let v = numbers(line_num, &args, 3)?;
+                    obj.vertices.push((v[0], v[1], v[2]));
+                }
+                "vt" => {
+                    // v and w are optional
+                    let mut uv = args.clone();
+                    uv.resize(uv.len().max(2), "0");
+                    let t = numbers(line_num, &uv, 2)?;
+                    obj.tex_coords.push((t[0], t[1]));
+                }
+                "vn" => {
+                    let n = numbers(line_num, &args, 3)?;
+                    obj.normals.push(Vec3(n[0], n[1], n[2]));
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(invalid(
+                            line_num,
+                            format!("a face needs at least 3 vertices, found {}", args.len()),
+                        ));
+                    }
+                    let face = args
+                        .iter()
+                        .map(|arg| {
+                            let mut parts = arg.split('/');
+                            let optional = |part: Option<&str>, len, what| match part {
+                                Some("") | None => Ok(None),
+                                Some(s) => index(line_num, s, len, what).map(Some),
+                            };
+                            Ok(FaceVertex {
+                                vertex: index(
+                                    line_num,
+                                    parts.next().unwrap_or(""),
+                                    obj.vertices.len(),
+                                    "vertex",
+                                )?,
+                                tex_coord: optional(
+                                    parts.next(),
+                                    obj.tex_coords.len(),
+                                    "texture coordinate",
+                                )?,
+                                normal: optional(parts.next(), obj.normals.len(), "normal")?,
+                            })
+                        })
+                        .collect::<io::Result<Vec<FaceVertex>>>()?;
+                    group.faces.push(face);
+                }
+                "o" | "g" => {
+                    let name = args.join(" ");
+                    let previous = std::mem::replace(
+                        &mut group,
+                        Group {
+                            name,
+                            faces: Vec::new(),
+                        },
+                    );
+                    obj.add_group(previous);
+                }
+                // materials, smoothing groups, lines, points...
+                _ => {}
+            }
+        }
+        obj.add_group(group);
+        Ok(obj)
+    }
+
+    /// Add the faces of `group`, merging them into the group of the same name if there is one
+    fn add_group(&mut self, group: Group) {
+        if group.faces.is_empty() {
+            return;
+        }
+        match self.groups.iter_mut().find(|g| g.name == group.name) {
+            Some(g) => g.faces.extend(group.faces),
+            None => self.groups.push(group),
+        }
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    /// Add the triangles of every face in `group` to polygon matrix `m`
+    pub fn add_group_to(&self, m: &mut Matrix, group: &Group) {
+        for face in group.faces.iter() {
+            let points: Vec<(f64, f64, f64)> =
+                face.iter().map(|v| self.vertices[v.vertex]).collect();
+            for [a, b, c] in triangulate(&points) {
+                m.append_polygon(points[a], points[b], points[c]);
+            }
+        }
+    }
+
+    /// Add the triangles of every face to polygon matrix `m`
+    pub fn add_to(&self, m: &mut Matrix) {
+        for group in self.groups.iter() {
+            self.add_group_to(m, group);
+        }
+    }
+}
+
+/// Split the polygon `points` into triangles with the same winding, returned as indices into `points`
+///
+/// Uses ear clipping, so concave polygons are fine as long as they're flat enough.
+/// Degenerate polygons fall back to a fan from the first point.
+fn triangulate(points: &[(f64, f64, f64)]) -> Vec<[usize; 3]> {
+    let fan = || (2..points.len()).map(|i| [0, i - 1, i]).collect();
+    if points.len() <= 3 {
+        return fan();
+    }
+
+    // Newell's method, the normal of the plane that best fits the polygon
+    let v = |i: usize| Vec3::from_pt(points[i]);
+    let normal = (0..points.len()).fold(Vec3(0., 0., 0.), |n, i| {
+        n + v(i).cross(v((i + 1) % points.len()))
+    });
+    // flatten onto the plane of the axes the normal is the least along
+    let (ax, ay, az) = (normal.0.abs(), normal.1.abs(), normal.2.abs());
+    let flat: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(x, y, z)| {
+            if az >= ax && az >= ay {
+                (x, y)
+            } else if ax >= ay {
+                (y, z)
+            } else {
+                (z, x)
+            }
+        })
+        .collect();
+    // whether the polygon goes counter-clockwise once flattened
+    let dominant = if az >= ax && az >= ay {
+        normal.2
+    } else if ax >= ay {
+        normal.0
+    } else {
+        normal.1
+    };
+    if dominant == 0. {
+        return fan();
+    }
+    let orientation = dominant.signum();
+
+    let cross = |a: usize, b: usize, c: usize| {
+        let (a, b, c) = (flat[a], flat[b], flat[c]);
+        ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) * orientation
+    };
+    let contains = |a: usize, b: usize, c: usize, p: usize| {
+        cross(a, b, p) >= 0. && cross(b, c, p) >= 0. && cross(c, a, p) >= 0.
+    };
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            cross(a, b, c) > 0.
+                && remaining
+                    .iter()
+                    .all(|&p| p == a || p == b || p == c || !contains(a, b, c, p))
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + n - 1) % n],
+                    remaining[i],
+                    remaining[(i + 1) % n],
+                ]);
+                remaining.remove(i);
+            }
+            None => return fan(),
+        }
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+impl Matrix {
+    /// Add every face of the OBJ file at `filepath` to this polygon matrix
+    pub fn add_mesh_obj(&mut self, filepath: &str) -> io::Result<()> {
+        Obj::load(filepath)?.add_to(self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "\
+# a unit cube, in quads
+o cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 -1
+v 1 0 -1
+v 1 1 -1
+v 0 1 -1
+vn 0 0 1
+vt 0 0
+f 1/1/1 2/1/1 3/1/1 4/1/1
+f 2 6 7 3
+f 6 5 8 7
+f 5 1 4 8
+g top
+f 4 3 7 8
+g bottom
+f -8 -4 -3 -7
+";
+
+    fn normals(m: &Matrix) -> Vec<Vec3> {
+        m.iter_by_row()
+            .collect::<Vec<_>>()
+            .chunks(3)
+            .map(|t| {
+                let v = |r: &[f64]| Vec3(r[0], r[1], r[2]);
+                (v(t[1]) - v(t[0])).cross(v(t[2]) - v(t[0])).norm()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cube() {
+        let obj = Obj::parse(CUBE).unwrap();
+        assert_eq!(
+            (8, 1, 1),
+            (obj.vertices.len(), obj.tex_coords.len(), obj.normals.len())
+        );
+        let names: Vec<&str> = obj.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(vec!["cube", "top", "bottom"], names);
+        assert_eq!(
+            FaceVertex {
+                vertex: 0,
+                tex_coord: Some(0),
+                normal: Some(0)
+            },
+            obj.groups[0].faces[0][0]
+        );
+        assert_eq!(vec![0, 4, 5, 1], {
+            let face = &obj.group("bottom").unwrap().faces[0];
+            face.iter().map(|v| v.vertex).collect::<Vec<_>>()
+        });
+
+        let mut m = Matrix::new_polygon_matrix();
+        obj.add_to(&mut m);
+        assert_eq!(12 * 3, m.nrows);
+        // every face points out of the cube
+        let center = Vec3(0.5, 0.5, -0.5);
+        for (t, n) in m
+            .iter_by_row()
+            .collect::<Vec<_>>()
+            .chunks(3)
+            .zip(normals(&m))
+        {
+            let p = Vec3(t[0][0], t[0][1], t[0][2]);
+            assert!((p - center).dot(n) > 0., "{:?} faces inwards", t);
+        }
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // an L, counter-clockwise, which a fan from the first point would get wrong
+        let points = [
+            (2., 1., 0.),
+            (1., 1., 0.),
+            (1., 2., 0.),
+            (0., 2., 0.),
+            (0., 0., 0.),
+            (2., 0., 0.),
+        ];
+        let triangles = triangulate(&points);
+        assert_eq!(4, triangles.len());
+        let area: f64 = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let (a, b, c) = (points[a], points[b], points[c]);
+                ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) / 2.
+            })
+            .inspect(|&area| assert!(area > 0.))
+            .sum();
+        assert_eq!(3., area);
+    }
+
+    #[test]
+    fn errors() {
+        for src in &[
+            "v 1 2",
+            "v 0 0 0\nf 1 2",
+            "v 0 0 0\nf 1 1 4",
+            "f a b c",
+            "vn 0 0 x",
+        ] {
+            assert!(Obj::parse(src).is_err(), "{}", src);
+        }
+        let e = Obj::parse("v 0 0 0\n\nf 1 1 0").unwrap_err();
+        assert_eq!(
+            "line 3: vertex index 0 is out of range, there are 1",
+            e.to_string()
+        );
+    }
+}
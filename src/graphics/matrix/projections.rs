@@ -2,7 +2,12 @@ use crate::graphics::{utils::mapper, matrix::Matrix};
 
 // https://developer.mozilla.org/en-US/docs/Web/API/WebGL_API/WebGL_model_view_projection#Perspective_matrix
 
-/// Construct a perspective projection matrix based
+/// Construct a perspective projection matrix
+///
+/// The camera is at the origin looking down -z. Points between the near and far planes
+/// end up with z in [-1, 1] after the perspective divide, -1 at the near plane.
+///
+/// The MDN matrix is for column vectors, this is its transpose since points are rows here.
 /// ## Arguments:
 ///        fov_rad - Field of view - the angle in radians of what's in view along the Y axis
 ///        aspect - Aspect Ratio - the ratio of the canvas, typically width / height
@@ -11,11 +16,6 @@ use crate::graphics::{utils::mapper, matrix::Matrix};
 ///
 #[rustfmt::skip]
 pub fn perspective(fov_rad: f64, aspect: f64, near: f64, far: f64) -> Matrix {
-
-    todo!("According to https://en.wikipedia.org/wiki/Graphics_pipeline#Projection, this projection matrix is the transposed version of what I should be using. Fix it.");
-
-    todo!("Impl clipping");
-
     let f = 1. / (fov_rad / 2.).tan();
     let range_inv = 1. / (near - far);
     Matrix::new(4, 4, vec![
@@ -24,7 +24,6 @@ pub fn perspective(fov_rad: f64, aspect: f64, near: f64, far: f64) -> Matrix {
         0.,         0., (near + far) * range_inv,       -1.,
         0.,         0., near * far * range_inv * 2.,    0.,
     ])
-
 }
 
 /// Construct an orthographic projection matrix
@@ -53,25 +52,28 @@ impl Matrix {
 
     /// Convert matrix in ndc coordinates to device coordinates
     ///
-    /// This should be used only after perspective divide and before rendered onto the canvas
+    /// This should be used only after perspective divide and before rendered onto the canvas.
+    /// z is flipped so that points closer to the camera have larger z, like on the canvas.
     pub fn ndc_n1to1_to_device(&mut self, width: f64, height: f64) {
         let map_width = mapper(-1., 1., 0., width);
         let map_height = mapper(-1., 1., 0., height);
 
         for row in self.mut_iter_by_row() {
-            row[0] = map_width(-row[0]);
+            row[0] = map_width(row[0]);
             row[1] = map_height(row[1]);
+            row[2] = -row[2];
         }
 
     }
+
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::graphics::{
+        matrix::{clip, transform},
         utils::display_edge_matrix,
-        matrix::transform
     };
 
     #[test]
@@ -81,18 +83,35 @@ mod tests {
         model.add_sphere((-100., 0., 0.), 75.);
         model.add_box((-80., -120., 0.), 75., 75., 75.);
         model.add_torus((-30., -335., 0.), 25., 175.);
-        let t = Matrix::ident(4)
-        // .mul(&transform::rotatex(30.))
-        // .mul(&transform::rotatey(-20.))
-        ._mul(&transform::mv(0., 250., 250.)
-        
-    );
-        let model = model._mul(&t);
+        // move the scene in front of the camera
+        let model = model._mul(&transform::mv(0., 250., -600.));
 
         // now apply perspective
-        let mut model = model._mul(&perspective(90., 1., 1., 500.));
+        let model = model._mul(&perspective(90f64.to_radians(), 1., 1., 1000.));
+        let mut model = model.clip_edges(&clip::FRUSTUM);
         model.perspective_divide();
 
+        for point in model.iter_by_row() {
+            assert!(point[..3].iter().all(|v| (-1. ..=1.).contains(v)), "{:?}", point);
+        }
+
+        // a point on the near plane goes to -1 and one on the far plane to 1
+        let planes = Matrix::new(2, 4, vec![0., 0., -1., 1., 0., 0., -1000., 1.]);
+        let mut planes = planes._mul(&perspective(1., 1., 1., 1000.));
+        planes.perspective_divide();
+        assert!((planes.get(0, 2).unwrap() + 1.).abs() < 1e-9);
+        assert!((planes.get(1, 2).unwrap() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    #[ignore]
+    fn display_perspective() {
+        let mut model = Matrix::new_edge_matrix();
+        model.add_sphere((110., 0., 0.), 75.);
+        model.add_box((-80., -120., 0.), 75., 75., 75.);
+        let model = model._mul(&transform::mv(0., 0., -400.));
+        let mut model = model._mul(&perspective(90f64.to_radians(), 1., 1., 1000.));
+        model.perspective_divide();
         display_edge_matrix(&model, true);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,247 @@
+//! Reading and writing polygon matrices as STL files, in ASCII or binary
+//!
+//! STL has no units and no colors, just triangles with counter-clockwise vertices seen from outside,
+//! which is the same winding as polygon matrices.
+//! Normals written out are the surface normals used to cull back faces, normalized.
+//! Normals read in are ignored, the winding decides which way triangles face.
+
+use super::Matrix;
+use crate::graphics::vector::Vec3;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Size of the header of binary STL files
+const HEADER_LEN: usize = 80;
+/// Size of a triangle in binary STL files: normal, 3 vertices and an attribute byte count
+const TRIANGLE_LEN: usize = 50;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Whether `bytes` are a binary STL file, from their size
+///
+/// Binary files can start with `solid` just like ASCII ones, so that isn't enough to tell.
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_LEN + 4 && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        bytes.len() == HEADER_LEN + 4 + count * TRIANGLE_LEN
+    }
+}
+
+fn read_binary(bytes: &[u8], m: &mut Matrix) {
+    for triangle in bytes[HEADER_LEN + 4..].chunks_exact(TRIANGLE_LEN) {
+        let f32_at = |i: usize| {
+            let b = &triangle[i..i + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+        };
+        // the vertices come after the normal
+        let p = |v: usize| {
+            let i = 12 + v * 12;
+            (f32_at(i), f32_at(i + 4), f32_at(i + 8))
+        };
+        m.append_polygon(p(0), p(1), p(2));
+    }
+}
+
+fn read_ascii(src: &str, m: &mut Matrix) -> io::Result<()> {
+    let mut vertices = Vec::with_capacity(3);
+    for (i, line) in src.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("vertex") => {
+                let coords = words
+                    .map(|w| {
+                        w.parse::<f64>().map_err(|_| {
+                            invalid(format!("line {}: expected a number, found `{}`", i + 1, w))
+                        })
+                    })
+                    .collect::<io::Result<Vec<f64>>>()?;
+                if coords.len() != 3 {
+                    return Err(invalid(format!(
+                        "line {}: a vertex needs 3 coordinates, found {}",
+                        i + 1,
+                        coords.len()
+                    )));
+                }
+                vertices.push((coords[0], coords[1], coords[2]));
+            }
+            Some("endfacet") => {
+                if vertices.len() != 3 {
+                    return Err(invalid(format!(
+                        "line {}: a facet needs 3 vertices, found {}",
+                        i + 1,
+                        vertices.len()
+                    )));
+                }
+                m.append_polygon(vertices[0], vertices[1], vertices[2]);
+                vertices.clear();
+            }
+            // solid, facet normal, outer loop, endloop, endsolid
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Matrix {
+    /// Add every triangle of the STL file at `filepath`, ASCII or binary, to this polygon matrix
+    pub fn add_mesh_stl(&mut self, filepath: &str) -> io::Result<()> {
+        let bytes = fs::read(filepath)?;
+        if is_binary(&bytes) {
+            read_binary(&bytes, self);
+            Ok(())
+        } else {
+            let src = String::from_utf8(bytes)
+                .map_err(|_| invalid(String::from("not an ASCII or binary STL file")))?;
+            read_ascii(&src, self)
+        }
+    }
+
+    /// Unit normal of every triangle, or zero for triangles without an area
+    fn stl_normals(&self) -> Vec<Vec3> {
+        self.surface_normals()
+            .into_iter()
+            .map(|n| {
+                if n.mag() > 0. {
+                    n.norm()
+                } else {
+                    Vec3(0., 0., 0.)
+                }
+            })
+            .collect()
+    }
+
+    /// Write this polygon matrix as an ASCII STL solid named `name`
+    pub fn write_stl_ascii(&self, name: &str, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "solid {}", name)?;
+        let rows: Vec<&[f64]> = self.iter_by_row().collect();
+        for (triangle, n) in rows.chunks_exact(3).zip(self.stl_normals()) {
+            writeln!(out, "  facet normal {:e} {:e} {:e}", n.0, n.1, n.2)?;
+            writeln!(out, "    outer loop")?;
+            for row in triangle {
+                writeln!(out, "      vertex {:e} {:e} {:e}", row[0], row[1], row[2])?;
+            }
+            writeln!(out, "    endloop")?;
+            writeln!(out, "  endfacet")?;
+        }
+        writeln!(out, "endsolid {}", name)
+    }
+
+    /// Write this polygon matrix as a binary STL file, with `name` in the header
+    pub fn write_stl_binary(&self, name: &str, out: &mut dyn Write) -> io::Result<()> {
+        let mut header = [b' '; HEADER_LEN];
+        let name = name.as_bytes();
+        let len = name.len().min(HEADER_LEN);
+        header[..len].copy_from_slice(&name[..len]);
+        out.write_all(&header)?;
+
+        let normals = self.stl_normals();
+        out.write_all(&(normals.len() as u32).to_le_bytes())?;
+        let rows: Vec<&[f64]> = self.iter_by_row().collect();
+        for (triangle, n) in rows.chunks_exact(3).zip(normals) {
+            for x in [n.0, n.1, n.2].iter() {
+                out.write_all(&(*x as f32).to_le_bytes())?;
+            }
+            for row in triangle {
+                for x in row[..3].iter() {
+                    out.write_all(&(*x as f32).to_le_bytes())?;
+                }
+            }
+            out.write_all(&[0, 0])?;
+        }
+        Ok(())
+    }
+
+    /// Save this polygon matrix as an STL file, named after the file
+    pub fn save_stl(&self, filepath: &str, format: StlFormat) -> io::Result<()> {
+        let name = Path::new(filepath)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut out = BufWriter::new(File::create(filepath)?);
+        match format {
+            StlFormat::Ascii => self.write_stl_ascii(&name, &mut out),
+            StlFormat::Binary => self.write_stl_binary(&name, &mut out),
+        }?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> Matrix {
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_box((0., 1., 0.), 1., 1., 1.);
+        m
+    }
+
+    fn round_trip(format: StlFormat) -> Matrix {
+        let path = std::env::temp_dir().join(format!("w8_solids_{:?}.stl", format));
+        let path = path.to_string_lossy();
+        cube().save_stl(&path, format).unwrap();
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_mesh_stl(&path).unwrap();
+        fs::remove_file(&*path).unwrap();
+        m
+    }
+
+    #[test]
+    fn round_trips() {
+        let original = cube();
+        for &format in [StlFormat::Ascii, StlFormat::Binary].iter() {
+            let m = round_trip(format);
+            assert_eq!(original.data, m.data, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let mut m = Matrix::new_polygon_matrix();
+        m.append_polygon((0., 0., 0.), (1., 0., 0.), (0., 1., 0.));
+        let mut out = Vec::new();
+        m.write_stl_ascii("tri", &mut out).unwrap();
+        assert_eq!(
+            "solid tri
+  facet normal 0e0 0e0 1e0
+    outer loop
+      vertex 0e0 0e0 0e0
+      vertex 1e0 0e0 0e0
+      vertex 0e0 1e0 0e0
+    endloop
+  endfacet
+endsolid tri
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn binary() {
+        let m = cube();
+        let mut out = Vec::new();
+        m.write_stl_binary("cube", &mut out).unwrap();
+        assert_eq!(HEADER_LEN + 4 + 12 * TRIANGLE_LEN, out.len());
+        assert!(is_binary(&out));
+        assert_eq!(b"cube ", &out[..5]);
+        assert_eq!(12, u32::from_le_bytes([out[80], out[81], out[82], out[83]]));
+
+        // the normal of the front, facing +z
+        let normal: Vec<f32> = out[84..96]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(vec![0., 0., 1.], normal);
+    }
+}
@@ -1,20 +1,30 @@
-#![allow(dead_code)]
-
+pub mod camera;
 pub mod canvas;
 pub mod colors;
 pub mod drawer;
+pub mod easing;
+pub mod gif;
+#[cfg(test)]
+pub(crate) mod golden;
+pub mod light;
+pub mod material;
 pub mod matrix;
 pub mod parametrics;
 pub mod parser;
-pub mod processes;
+pub mod png;
+pub mod recording;
+pub mod render;
+pub mod svg;
+pub mod terminal;
 pub mod utils;
 pub mod vector;
+pub mod zlib;
 pub mod img;
 
 
 // re-exports
 pub use canvas::Canvas;
-pub use colors::{HSL, RGB};
+pub use colors::RGB;
 pub use matrix::Matrix;
 pub use drawer::Drawer;
 pub use img::PPMImg;
\ No newline at end of file
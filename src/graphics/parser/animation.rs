@@ -0,0 +1,242 @@
+//! First pass over an animated script: works out the value of every knob in every frame
+//!
+//! A script is animated if it has a `frames` command.
+//! `vary knob start_frame end_frame start_val end_val [easing]` changes a knob
+//! over the frames from `start_frame` to `end_frame`, both included.
+//! Between and after its `vary` ranges a knob holds its last value,
+//! and before its first range it holds the first start value.
+//!
+//! A knob can instead be given with keyframes, `key knob frame value [easing]`,
+//! which are interpolated like `Keyframes`. A knob can't use both.
+
+use super::ast::{Command, Statement};
+use crate::graphics::easing::Keyframes;
+use std::collections::HashMap;
+
+/// Name used for frames when the script has no `basename`
+pub const DEFAULT_BASENAME: &str = "frame";
+
+/// Knob values for each frame of an animation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub frames: usize,
+    pub basename: String,
+    /// Knob values for each frame, indexed by frame number
+    pub knobs: Vec<HashMap<String, f64>>,
+    /// Problems that don't stop the animation, like a missing `basename`
+    pub warnings: Vec<String>,
+}
+
+impl Animation {
+    /// Compute the knob table for `statements`
+    ///
+    /// Returns `None` if the script isn't animated.
+    /// Errors point at the offending statement.
+    pub fn new(statements: &[Statement]) -> Result<Option<Self>, (&Statement, String)> {
+        let mut frames = None;
+        let mut basename = None;
+        for statement in statements {
+            match &statement.command {
+                Command::Frames(n) => {
+                    if *n == 0 {
+                        return Err((
+                            statement,
+                            String::from("an animation needs at least 1 frame"),
+                        ));
+                    }
+                    frames = Some(*n);
+                }
+                Command::Basename(name) => basename = Some(name.clone()),
+                _ => {}
+            }
+        }
+
+        let frames = match frames {
+            Some(frames) => frames,
+            None => {
+                return match statements
+                    .iter()
+                    .find(|s| matches!(s.command, Command::Vary { .. } | Command::Key { .. }))
+                {
+                    Some(s) if matches!(s.command, Command::Key { .. }) => {
+                        Err((s, String::from("key used without frames")))
+                    }
+                    Some(s) => Err((s, String::from("vary used without frames"))),
+                    None => Ok(None),
+                }
+            }
+        };
+        let mut warnings = vec![];
+        let basename = basename.unwrap_or_else(|| {
+            warnings.push(format!("no basename given, using \"{}\"", DEFAULT_BASENAME));
+            DEFAULT_BASENAME.to_string()
+        });
+
+        // value of each knob in each frame, None where no vary covers the frame
+        let mut table: HashMap<&str, Vec<Option<f64>>> = HashMap::new();
+        let mut keyframes: HashMap<&str, Keyframes> = HashMap::new();
+        for statement in statements {
+            if let Command::Key {
+                knob,
+                frame,
+                value,
+                easing,
+            } = &statement.command
+            {
+                if *frame >= frames {
+                    return Err((
+                        statement,
+                        format!("frame {} is past the last frame ({})", frame, frames - 1),
+                    ));
+                }
+                keyframes
+                    .entry(knob)
+                    .or_default()
+                    .add_key(*frame as f64, *value, *easing);
+            } else if let Command::Vary {
+                knob,
+                start_frame,
+                end_frame,
+                start_val,
+                end_val,
+                easing,
+            } = &statement.command
+            {
+                let (start, end) = (*start_frame, *end_frame);
+                if start > end {
+                    return Err((
+                        statement,
+                        format!("start frame {} is after end frame {}", start, end),
+                    ));
+                }
+                if end >= frames {
+                    return Err((
+                        statement,
+                        format!("end frame {} is past the last frame ({})", end, frames - 1),
+                    ));
+                }
+
+                let values = table.entry(knob).or_insert_with(|| vec![None; frames]);
+                for (frame, value) in values.iter_mut().enumerate().take(end + 1).skip(start) {
+                    *value = Some(if start == end {
+                        *end_val
+                    } else {
+                        let t = (frame - start) as f64 / (end - start) as f64;
+                        easing.interpolate(*start_val, *end_val, t)
+                    });
+                }
+            }
+        }
+
+        if let Some(key) = statements.iter().find(
+            |s| matches!(&s.command, Command::Key { knob, .. } if table.contains_key(knob.as_str())),
+        ) {
+            return Err((key, String::from("knob is already changed with vary")));
+        }
+
+        let mut knobs = vec![HashMap::new(); frames];
+        for (name, values) in table {
+            // there is at least one value since each vary covers a frame
+            let mut last = values.iter().flatten().next().copied().unwrap();
+            for (frame, value) in values.into_iter().enumerate() {
+                last = value.unwrap_or(last);
+                knobs[frame].insert(name.to_string(), last);
+            }
+        }
+        for (name, keys) in keyframes {
+            for (frame, knobs) in knobs.iter_mut().enumerate() {
+                // there is at least one key
+                knobs.insert(name.to_string(), keys.value_at(frame as f64).unwrap());
+            }
+        }
+
+        Ok(Some(Animation {
+            frames,
+            basename,
+            knobs,
+            warnings,
+        }))
+    }
+
+    /// File name of `frame`, numbered so the frames sort in order
+    pub fn frame_name(&self, frame: usize) -> String {
+        let digits = (self.frames - 1).to_string().len().max(3);
+        format!("{}{:0width$}", self.basename, frame, width = digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::grammar::{parse, Dialect};
+    use super::*;
+
+    fn animation(src: &str) -> Result<Option<Animation>, String> {
+        let statements = parse(src, "test", Dialect::Mdl).unwrap();
+        Animation::new(&statements).map_err(|(s, e)| format!("{}: {}", s.line, e))
+    }
+
+    fn knob(a: &Animation, name: &str) -> Vec<f64> {
+        a.knobs.iter().map(|k| k[name]).collect()
+    }
+
+    #[test]
+    fn not_animated() {
+        assert_eq!(Ok(None), animation("push\nsphere 0 0 0 10"));
+    }
+
+    #[test]
+    fn vary_knobs() {
+        let a = animation(
+            "frames 6
+basename spin
+vary k 1 3 0 1
+vary k 4 5 10 20
+vary j 2 2 5 5",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(6, a.frames);
+        assert_eq!("spin", a.basename);
+        assert_eq!(vec![0., 0., 0.5, 1., 10., 20.], knob(&a, "k"));
+        assert_eq!(vec![5.; 6], knob(&a, "j"));
+        assert_eq!("spin004", a.frame_name(4));
+        assert!(a.warnings.is_empty());
+
+        let a = animation("frames 2").unwrap().unwrap();
+        assert_eq!(DEFAULT_BASENAME, a.basename);
+        assert_eq!(vec!["no basename given, using \"frame\""], a.warnings);
+    }
+
+    #[test]
+    fn easing_and_keys() {
+        let a = animation(
+            "frames 5
+vary k 0 4 0 1 ease_in
+key j 1 10 step
+key j 3 20 bezier 0.5 0 0.5 1",
+        )
+        .unwrap()
+        .unwrap();
+        let k = knob(&a, "k");
+        assert!(k[2] < 0.5);
+        assert_eq!(1., k[4]);
+        assert_eq!(vec![10., 10., 10., 20., 20.], knob(&a, "j"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err(String::from("2: vary used without frames")),
+            animation("push\nvary k 0 1 0 1")
+        );
+        assert!(animation("frames 10\nvary k 5 2 0 1").is_err());
+        assert!(animation("frames 10\nvary k 0 10 0 1").is_err());
+        assert!(animation("frames 0").is_err());
+        assert!(animation("key k 0 1").is_err());
+        assert!(animation("frames 2\nkey k 2 1").is_err());
+        assert_eq!(
+            Err(String::from("3: knob is already changed with vary")),
+            animation("frames 2\nvary k 0 1 0 1\nkey k 0 1")
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Commands produced by parsing a script
+
+use crate::graphics::{
+    easing::Easing, light::ShadingMode, material::Material, matrix::dim3::Polyhedron,
+    render::FillMode, RGB,
+};
+
+pub type Point = (f64, f64, f64);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// A single command in a script
+///
+/// `constants` name a material defined with `Constants`,
+/// `coord` name a coordinate system saved with `SaveCoordSystem`,
+/// and `knob` name a knob whose value scales the transformation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    // coordinate stack
+    Push,
+    Pop,
+    Move {
+        delta: Point,
+        knob: Option<String>,
+    },
+    Scale {
+        factors: Point,
+        knob: Option<String>,
+    },
+    Rotate {
+        axis: Axis,
+        degrees: f64,
+        knob: Option<String>,
+    },
+    SaveCoordSystem(String),
+
+    // solids
+    Box {
+        constants: Option<String>,
+        corner: Point,
+        dx: f64,
+        dy: f64,
+        dz: f64,
+        coord: Option<String>,
+    },
+    Sphere {
+        constants: Option<String>,
+        center: Point,
+        radius: f64,
+        coord: Option<String>,
+    },
+    Torus {
+        constants: Option<String>,
+        center: Point,
+        radius1: f64,
+        radius2: f64,
+        coord: Option<String>,
+    },
+    Ellipsoid {
+        constants: Option<String>,
+        center: Point,
+        radii: Point,
+        coord: Option<String>,
+    },
+    Cylinder {
+        constants: Option<String>,
+        base: Point,
+        radius: f64,
+        height: f64,
+        coord: Option<String>,
+    },
+    /// A cone cut at `radius_top`, which is 0 for a whole cone
+    Cone {
+        constants: Option<String>,
+        base: Point,
+        radius_bottom: f64,
+        radius_top: f64,
+        height: f64,
+        coord: Option<String>,
+    },
+    Pyramid {
+        constants: Option<String>,
+        base: Point,
+        side: f64,
+        height: f64,
+        coord: Option<String>,
+    },
+    Polyhedron {
+        constants: Option<String>,
+        polyhedron: Polyhedron,
+        center: Point,
+        radius: f64,
+        coord: Option<String>,
+    },
+    Plane {
+        constants: Option<String>,
+        corner: Point,
+        width: f64,
+        height: f64,
+        /// Columns and rows of squares the plane is split into
+        divisions: (usize, usize),
+        coord: Option<String>,
+    },
+    Mesh {
+        constants: Option<String>,
+        filename: String,
+        coord: Option<String>,
+    },
+
+    // edges
+    Line {
+        p0: Point,
+        coord0: Option<String>,
+        p1: Point,
+        coord1: Option<String>,
+    },
+    Circle {
+        center: Point,
+        radius: f64,
+    },
+    Hermite {
+        p0: (f64, f64),
+        p1: (f64, f64),
+        r0: (f64, f64),
+        r1: (f64, f64),
+    },
+    Bezier {
+        p0: (f64, f64),
+        p1: (f64, f64),
+        p2: (f64, f64),
+        p3: (f64, f64),
+    },
+
+    // lighting
+    Constants {
+        name: String,
+        material: Material,
+    },
+    /// Directional light, `location` points from the surfaces towards the light
+    Light {
+        name: String,
+        color: RGB,
+        location: Point,
+    },
+    PointLight {
+        name: String,
+        color: RGB,
+        position: Point,
+    },
+    Ambient(RGB),
+    Shading(ShadingMode),
+    /// Where the colors of polygons come from
+    Fill(FillMode),
+    /// Perspective camera at `eye` looking at `aim`, with a vertical field of view in degrees
+    Camera {
+        eye: Point,
+        aim: Point,
+        fov: Option<f64>,
+    },
+    /// Orthographic camera showing the box from (left, bottom, -far) to (right, top, -near)
+    Ortho {
+        left: f64,
+        right: f64,
+        bottom: f64,
+        top: f64,
+        near: f64,
+        far: f64,
+    },
+
+    /// Size of the scene the script is drawn in, also the size of the image unless it is overridden
+    Size {
+        width: usize,
+        height: usize,
+    },
+
+    // animation
+    Frames(usize),
+    Basename(String),
+    Vary {
+        knob: String,
+        start_frame: usize,
+        end_frame: usize,
+        start_val: f64,
+        end_val: f64,
+        easing: Easing,
+    },
+    /// Keyframe: `knob` is `value` at `frame`, eased with `easing` until its next key
+    Key {
+        knob: String,
+        frame: usize,
+        value: f64,
+        easing: Easing,
+    },
+    Set {
+        knob: String,
+        value: f64,
+    },
+    SetKnobs(f64),
+
+    // output
+    Display,
+    Save(String),
+    Clear,
+}
+
+/// A command and the line it starts on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub line: usize,
+    pub command: Command,
+}
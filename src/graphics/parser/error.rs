@@ -0,0 +1,118 @@
+//! Errors found while parsing or running a script
+
+use std::{error::Error, fmt};
+
+/// An error at a position in a script
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub file: String,
+    /// Line number starting from 1, or 0 if the error isn't tied to a line
+    pub line: usize,
+    /// Column number starting from 1
+    pub column: usize,
+    /// Text of the offending token, empty at the end of a line
+    pub token: String,
+    pub message: String,
+    /// Arguments expected by the command, e.g. `sphere [constants] x y z r [coord]`
+    pub hint: Option<String>,
+    /// The whole line the error is on, used to point at the token
+    pub source_line: String,
+}
+
+impl ParseError {
+    /// An error that isn't tied to a line, like failing to read the file
+    pub fn file_error(file: &str, message: String) -> Self {
+        ParseError {
+            file: file.to_string(),
+            line: 0,
+            column: 0,
+            token: String::new(),
+            message,
+            hint: None,
+            source_line: String::new(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    /// Prints the error with a caret under the source, like
+    ///
+    /// ```text
+    /// error: expected radius, found end of line
+    /// --> script.mdl:3:13
+    ///   |
+    /// 3 | sphere 0 0 0
+    ///   |             ^
+    ///   = usage: sphere [constants] x y z r [coord]
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+        if self.line == 0 {
+            return write!(f, " --> {}", self.file);
+        }
+
+        let num = self.line.to_string();
+        let pad = " ".repeat(num.len());
+        writeln!(f, "{}--> {}:{}:{}", pad, self.file, self.line, self.column)?;
+        writeln!(f, "{} |", pad)?;
+        writeln!(f, "{} | {}", num, self.source_line)?;
+        write!(
+            f,
+            "{} | {}{}",
+            pad,
+            " ".repeat(self.column.saturating_sub(1)),
+            "^".repeat(self.token.chars().count().max(1))
+        )?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n{} = usage: {}", pad, hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
+
+/// All errors found in a script
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseErrors(pub Vec<ParseError>);
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in self.0.iter() {
+            writeln!(f, "{}\n", e)?;
+        }
+        match self.0.len() {
+            1 => write!(f, "1 error"),
+            n => write!(f, "{} errors", n),
+        }
+    }
+}
+
+impl Error for ParseErrors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caret_under_token() {
+        let e = ParseError {
+            file: String::from("a.mdl"),
+            line: 12,
+            column: 8,
+            token: String::from("w"),
+            message: String::from("expected rotation axis (x, y or z), found \"w\""),
+            hint: Some(String::from("rotate x|y|z degrees [knob]")),
+            source_line: String::from("rotate w 30"),
+        };
+        assert_eq!(
+            "error: expected rotation axis (x, y or z), found \"w\"
+  --> a.mdl:12:8
+   |
+12 | rotate w 30
+   |        ^
+   = usage: rotate x|y|z degrees [knob]",
+            e.to_string()
+        );
+    }
+}
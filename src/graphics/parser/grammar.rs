@@ -0,0 +1,1010 @@
+//! Turns tokens into commands
+//!
+//! Every command is on a single line: the command name followed by its arguments.
+//! Arguments in brackets are optional:
+//!
+//! - `push`, `pop`
+//! - `move x y z [knob]`, `scale x y z [knob]`, `rotate x|y|z degrees [knob]`
+//! - `save_coord_system name`
+//! - `box [constants] x y z dx dy dz [coord]`
+//! - `sphere [constants] x y z r [coord]`
+//! - `torus [constants] x y z r1 r2 [coord]`
+//! - `ellipsoid [constants] x y z rx ry rz [coord]`
+//! - `cylinder|cone [constants] x y z r height [coord]`
+//! - `frustum [constants] x y z r_bottom r_top height [coord]`
+//! - `pyramid [constants] x y z side height [coord]`
+//! - `tetrahedron|octahedron|icosahedron [constants] x y z r [coord]`
+//! - `plane [constants] x y z width height [columns rows] [coord]`
+//! - `mesh [constants] :filename [coord]`
+//! - `line [constants] x0 y0 z0 [coord0] x1 y1 z1 [coord1]`
+//! - `circle x y z r`, `hermite x0 y0 x1 y1 rx0 ry0 rx1 ry1`, `bezier x0 y0 x1 y1 x2 y2 x3 y3`
+//! - `constants name kar kdr ksr kag kdg ksg kab kdb ksb [r g b]`
+//! - `light name r g b x y z`, `point_light name r g b x y z`, `ambient r g b`
+//! - `shading flat|gouraud|phong`
+//! - `fill lit|random [seed]|fixed r g b|palette r g b [r g b ...]|normals`
+//! - `frames n`, `basename name`, `vary knob start_frame end_frame start_val end_val [easing]`
+//! - `key knob frame value [easing]`
+//! - `set knob value`, `set_knobs value`
+//! - `display`, `save filename`, `clear`
+//!
+//! `easing` is one of `linear`, `ease_in`, `ease_out`, `ease_in_out`, `exponential`, `sine`, `step`
+//! or `bezier x1 y1 x2 y2`.
+//!
+//! In the legacy dialect, arguments are on the line after the command instead.
+
+use super::{
+    ast::{Axis, Command, Point, Statement},
+    error::{ParseError, ParseErrors},
+    lexer::{tokenize, Token, TokenKind},
+};
+use crate::graphics::{
+    easing::Easing, light::ShadingMode, material::Material, matrix::dim3::Polyhedron,
+    render::FillMode, RGB,
+};
+use std::collections::HashSet;
+
+/// Script formats understood by the parser
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dialect {
+    /// One command per line, with arguments on the same line
+    Mdl,
+    /// Arguments on the line following their command
+    Legacy,
+}
+
+/// Returns true if `command` needs arguments
+fn takes_args(command: &str) -> bool {
+    !matches!(command, "push" | "pop" | "display" | "clear")
+}
+
+impl Dialect {
+    /// Guess the dialect of `src`
+    ///
+    /// It is legacy if a command that needs arguments is alone on its line.
+    pub fn detect(src: &str) -> Dialect {
+        let tokens = tokenize(src);
+        let alone = tokens
+            .windows(3)
+            .any(|w| match (&w[0].kind, &w[1].kind, &w[2].kind) {
+                (TokenKind::Newline, TokenKind::Word(cmd), TokenKind::Newline) => takes_args(cmd),
+                _ => false,
+            });
+        // the first line isn't preceded by a new line
+        let first_alone = match (tokens.first(), tokens.get(1)) {
+            (Some(first), Some(second)) => match (&first.kind, &second.kind) {
+                (TokenKind::Word(cmd), TokenKind::Newline) => takes_args(cmd),
+                _ => false,
+            },
+            _ => false,
+        };
+
+        if alone || first_alone {
+            Dialect::Legacy
+        } else {
+            Dialect::Mdl
+        }
+    }
+}
+
+/// Returns the arguments `command` takes, used as a hint in errors
+pub fn usage(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "push" => "push",
+        "pop" => "pop",
+        "move" => "move x y z [knob]",
+        "scale" => "scale x y z [knob]",
+        "rotate" => "rotate x|y|z degrees [knob]",
+        "save_coord_system" => "save_coord_system name",
+        "box" => "box [constants] x y z dx dy dz [coord]",
+        "sphere" => "sphere [constants] x y z r [coord]",
+        "torus" => "torus [constants] x y z r1 r2 [coord]",
+        "ellipsoid" => "ellipsoid [constants] x y z rx ry rz [coord]",
+        "cylinder" => "cylinder [constants] x y z r height [coord]",
+        "cone" => "cone [constants] x y z r height [coord]",
+        "frustum" => "frustum [constants] x y z r_bottom r_top height [coord]",
+        "pyramid" => "pyramid [constants] x y z side height [coord]",
+        "tetrahedron" => "tetrahedron [constants] x y z r [coord]",
+        "octahedron" => "octahedron [constants] x y z r [coord]",
+        "icosahedron" => "icosahedron [constants] x y z r [coord]",
+        "plane" => "plane [constants] x y z width height [columns rows] [coord]",
+        "mesh" => "mesh [constants] :filename [coord]",
+        "line" => "line [constants] x0 y0 z0 [coord0] x1 y1 z1 [coord1]",
+        "circle" => "circle x y z r",
+        "hermite" => "hermite x0 y0 x1 y1 rx0 ry0 rx1 ry1",
+        "bezier" => "bezier x0 y0 x1 y1 x2 y2 x3 y3",
+        "constants" => "constants name kar kdr ksr kag kdg ksg kab kdb ksb [r g b]",
+        "light" => "light name r g b x y z",
+        "point_light" => "point_light name r g b x y z",
+        "ambient" => "ambient r g b",
+        "shading" => "shading flat|gouraud|phong",
+        "fill" => "fill lit|random [seed]|fixed r g b|palette r g b [r g b ...]|normals",
+        "camera" => "camera eye_x eye_y eye_z aim_x aim_y aim_z [fov]",
+        "ortho" => "ortho left right bottom top near far",
+        "size" => "size width height",
+        "frames" => "frames n",
+        "basename" => "basename name",
+        "vary" => "vary knob start_frame end_frame start_val end_val [easing]",
+        "key" => "key knob frame value [easing]",
+        "set" => "set knob value",
+        "set_knobs" => "set_knobs value",
+        "display" => "display",
+        "save" => "save filename",
+        "clear" => "clear",
+        _ => return None,
+    })
+}
+
+/// Parse `src` written in `dialect`
+///
+/// `file` is only used in errors. Parsing continues after an error,
+/// so all errors in the script are returned at once.
+pub fn parse(src: &str, file: &str, dialect: Dialect) -> Result<Vec<Statement>, ParseErrors> {
+    let tokens = tokenize(src);
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        dialect,
+        file,
+        lines: src.lines().collect(),
+        command: None,
+        constants: HashSet::new(),
+        coord_systems: HashSet::new(),
+        depth: 0,
+    };
+
+    let mut statements = vec![];
+    let mut errors = vec![];
+    loop {
+        match parser.statement() {
+            Ok(Some(statement)) => statements.push(statement),
+            Ok(None) => break,
+            Err(e) => {
+                errors.push(*e);
+                parser.recover();
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(statements)
+    } else {
+        Err(ParseErrors(errors))
+    }
+}
+
+// boxed since errors are large and most results are small
+type PResult<T> = Result<T, Box<ParseError>>;
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Word(w) => format!("\"{}\"", w),
+        TokenKind::Number(n) => format!("number {}", n),
+        TokenKind::Newline => String::from("end of line"),
+        TokenKind::Eof => String::from("end of file"),
+    }
+}
+
+/// Names that have to be defined before they are used
+#[derive(Copy, Clone)]
+enum Name {
+    Constants,
+    CoordSystem,
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    dialect: Dialect,
+    file: &'a str,
+    lines: Vec<&'a str>,
+    /// Name of the command being parsed
+    command: Option<&'a str>,
+    /// Names defined so far
+    constants: HashSet<String>,
+    coord_systems: HashSet<String>,
+    /// Number of pushes not popped yet
+    depth: usize,
+}
+
+// token helpers
+//
+// Helpers only advance past a token if it is what they expect
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &'a Token {
+        &self.tokens[self.pos]
+    }
+
+    /// Advance and return the current token; stays at Eof once reached
+    fn next(&mut self) -> &'a Token {
+        let token = &self.tokens[self.pos];
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Skip the rest of the command after an error
+    fn recover(&mut self) {
+        let bad = self.peek();
+        while !matches!(self.peek().kind, TokenKind::Newline | TokenKind::Eof) {
+            self.next();
+        }
+        if self.dialect == Dialect::Legacy && self.command.is_none() && bad.line == self.peek().line
+        {
+            // an unknown command may have its arguments on the next line
+            self.next();
+            if let TokenKind::Number(_) = self.peek().kind {
+                while !matches!(self.peek().kind, TokenKind::Newline | TokenKind::Eof) {
+                    self.next();
+                }
+            }
+        }
+    }
+
+    fn error_msg(&self, token: &Token, message: String) -> Box<ParseError> {
+        Box::new(ParseError {
+            file: self.file.to_string(),
+            line: token.line,
+            column: token.col,
+            token: token.text.clone(),
+            message,
+            hint: self.command.and_then(usage).map(String::from),
+            source_line: self
+                .lines
+                .get(token.line.wrapping_sub(1))
+                .copied()
+                .unwrap_or("")
+                .to_string(),
+        })
+    }
+
+    fn error<T>(&self, token: &Token, expected: &str) -> PResult<T> {
+        Err(self.error_msg(
+            token,
+            format!("expected {}, found {}", expected, describe(&token.kind)),
+        ))
+    }
+
+    fn number(&mut self, what: &str) -> PResult<f64> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Number(n) => {
+                self.next();
+                Ok(n)
+            }
+            _ => self.error(token, what),
+        }
+    }
+
+    /// A number between 0 and 1
+    fn unit(&mut self, what: &str) -> PResult<f64> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Number(n) if (0. ..=1.).contains(&n) => {
+                self.next();
+                Ok(n)
+            }
+            _ => self.error(token, &format!("{} (between 0 and 1)", what)),
+        }
+    }
+
+    /// A non-negative integer
+    fn uint(&mut self, what: &str) -> PResult<usize> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Number(n) if n >= 0. && n.fract() == 0. => {
+                self.next();
+                Ok(n as usize)
+            }
+            _ => self.error(token, &format!("{} (a whole number)", what)),
+        }
+    }
+
+    /// A positive integer
+    fn size(&mut self, what: &str) -> PResult<usize> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Number(n) if n >= 1. && n.fract() == 0. => {
+                self.next();
+                Ok(n as usize)
+            }
+            _ => self.error(token, &format!("{} (a whole number larger than 0)", what)),
+        }
+    }
+
+    fn word(&mut self, what: &str) -> PResult<String> {
+        let token = self.peek();
+        match &token.kind {
+            TokenKind::Word(w) => {
+                self.next();
+                Ok(w.clone())
+            }
+            _ => self.error(token, what),
+        }
+    }
+
+    /// Consume and return the next token if it is a word
+    fn opt_word(&mut self) -> Option<String> {
+        match &self.peek().kind {
+            TokenKind::Word(w) => {
+                self.next();
+                Some(w.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Check that `name` has been defined
+    fn check_defined(&self, token: &Token, kind: Name, name: &str) -> PResult<()> {
+        let (defined, what) = match kind {
+            Name::Constants => (&self.constants, "constants"),
+            Name::CoordSystem => (&self.coord_systems, "coordinate system"),
+        };
+        if defined.contains(name) {
+            Ok(())
+        } else {
+            Err(self.error_msg(token, format!("unknown {} \"{}\"", what, name)))
+        }
+    }
+
+    /// Like `opt_word`, but the name must have been defined
+    fn opt_name(&mut self, kind: Name) -> PResult<Option<String>> {
+        let token = self.peek();
+        match self.opt_word() {
+            Some(name) => {
+                self.check_defined(token, kind, &name)?;
+                Ok(Some(name))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn point(&mut self) -> PResult<Point> {
+        Ok((self.number("x")?, self.number("y")?, self.number("z")?))
+    }
+
+    fn point2(&mut self) -> PResult<(f64, f64)> {
+        Ok((self.number("x")?, self.number("y")?))
+    }
+
+    fn color(&mut self) -> PResult<RGB> {
+        let mut channel = |what| -> PResult<u16> {
+            Ok(self.number(what)?.round().max(0.).min(u16::MAX as f64) as u16)
+        };
+        Ok(RGB::new(
+            channel("red")?,
+            channel("green")?,
+            channel("blue")?,
+        ))
+    }
+
+    /// An optional easing curve, linear if there is none
+    fn opt_easing(&mut self) -> PResult<Easing> {
+        let token = self.peek();
+        let name = match &token.kind {
+            TokenKind::Word(w) => w.as_str(),
+            _ => return Ok(Easing::Linear),
+        };
+        let easing = match name {
+            "linear" => Easing::Linear,
+            "ease_in" => Easing::EaseIn,
+            "ease_out" => Easing::EaseOut,
+            "ease_in_out" => Easing::EaseInOut,
+            "exponential" => Easing::Exponential,
+            "sine" => Easing::Sine,
+            "step" => Easing::Step,
+            "bezier" => {
+                self.next();
+                let x1 = self.unit("x1")?;
+                let y1 = self.number("y1")?;
+                let x2 = self.unit("x2")?;
+                let y2 = self.number("y2")?;
+                return Ok(Easing::Bezier(x1, y1, x2, y2));
+            }
+            _ => return self.error(token, "easing"),
+        };
+        self.next();
+        Ok(easing)
+    }
+
+    /// Expect the end of a command
+    fn end(&mut self) -> PResult<()> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Newline | TokenKind::Eof => {
+                self.next();
+                Ok(())
+            }
+            _ => Err(self.error_msg(
+                token,
+                format!("too many arguments, found {}", describe(&token.kind)),
+            )),
+        }
+    }
+}
+
+// grammar
+impl<'a> Parser<'a> {
+    /// Parse the next statement, or return None at the end of the file
+    fn statement(&mut self) -> PResult<Option<Statement>> {
+        self.command = None;
+        while self.peek().kind == TokenKind::Newline {
+            self.next();
+        }
+        let token = self.peek();
+        let name = match &token.kind {
+            TokenKind::Eof => return Ok(None),
+            TokenKind::Word(w) if usage(w).is_some() => w.as_str(),
+            TokenKind::Word(w) => {
+                return Err(self.error_msg(token, format!("unknown command \"{}\"", w)))
+            }
+            _ => return self.error(token, "a command"),
+        };
+        self.next();
+        self.command = Some(name);
+
+        if self.dialect == Dialect::Legacy
+            && takes_args(name)
+            && self.peek().kind == TokenKind::Newline
+        {
+            // arguments are on the next line
+            self.next();
+        }
+
+        let command = self.command(name, token)?;
+        self.end()?;
+        Ok(Some(Statement {
+            line: token.line,
+            command,
+        }))
+    }
+
+    fn command(&mut self, name: &str, token: &Token) -> PResult<Command> {
+        Ok(match name {
+            "push" => {
+                self.depth += 1;
+                Command::Push
+            }
+            "pop" => {
+                if self.depth == 0 {
+                    return Err(self.error_msg(token, String::from("pop without matching push")));
+                }
+                self.depth -= 1;
+                Command::Pop
+            }
+            "move" => Command::Move {
+                delta: self.point()?,
+                knob: self.opt_word(),
+            },
+            "scale" => Command::Scale {
+                factors: self.point()?,
+                knob: self.opt_word(),
+            },
+            "rotate" => {
+                let axis_token = self.peek();
+                let axis = match self.word("rotation axis (x, y or z)")?.as_str() {
+                    "x" | "X" => Axis::X,
+                    "y" | "Y" => Axis::Y,
+                    "z" | "Z" => Axis::Z,
+                    _ => return self.error(axis_token, "rotation axis (x, y or z)"),
+                };
+                Command::Rotate {
+                    axis,
+                    degrees: self.number("degrees")?,
+                    knob: self.opt_word(),
+                }
+            }
+            "save_coord_system" => {
+                let name = self.word("coordinate system name")?;
+                self.coord_systems.insert(name.clone());
+                Command::SaveCoordSystem(name)
+            }
+
+            "box" => Command::Box {
+                constants: self.opt_name(Name::Constants)?,
+                corner: self.point()?,
+                dx: self.number("width")?,
+                dy: self.number("height")?,
+                dz: self.number("depth")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "sphere" => Command::Sphere {
+                constants: self.opt_name(Name::Constants)?,
+                center: self.point()?,
+                radius: self.number("radius")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "torus" => Command::Torus {
+                constants: self.opt_name(Name::Constants)?,
+                center: self.point()?,
+                radius1: self.number("inner radius")?,
+                radius2: self.number("outer radius")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "ellipsoid" => Command::Ellipsoid {
+                constants: self.opt_name(Name::Constants)?,
+                center: self.point()?,
+                radii: (
+                    self.number("x radius")?,
+                    self.number("y radius")?,
+                    self.number("z radius")?,
+                ),
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "cylinder" => Command::Cylinder {
+                constants: self.opt_name(Name::Constants)?,
+                base: self.point()?,
+                radius: self.number("radius")?,
+                height: self.number("height")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "cone" => Command::Cone {
+                constants: self.opt_name(Name::Constants)?,
+                base: self.point()?,
+                radius_bottom: self.number("radius")?,
+                radius_top: 0.,
+                height: self.number("height")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "frustum" => Command::Cone {
+                constants: self.opt_name(Name::Constants)?,
+                base: self.point()?,
+                radius_bottom: self.number("bottom radius")?,
+                radius_top: self.number("top radius")?,
+                height: self.number("height")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "pyramid" => Command::Pyramid {
+                constants: self.opt_name(Name::Constants)?,
+                base: self.point()?,
+                side: self.number("side")?,
+                height: self.number("height")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "tetrahedron" | "octahedron" | "icosahedron" => Command::Polyhedron {
+                constants: self.opt_name(Name::Constants)?,
+                polyhedron: match name {
+                    "tetrahedron" => Polyhedron::Tetrahedron,
+                    "octahedron" => Polyhedron::Octahedron,
+                    _ => Polyhedron::Icosahedron,
+                },
+                center: self.point()?,
+                radius: self.number("radius")?,
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "plane" => Command::Plane {
+                constants: self.opt_name(Name::Constants)?,
+                corner: self.point()?,
+                width: self.number("width")?,
+                height: self.number("height")?,
+                divisions: match self.peek().kind {
+                    TokenKind::Number(_) => (self.size("columns")?, self.size("rows")?),
+                    _ => (1, 1),
+                },
+                coord: self.opt_name(Name::CoordSystem)?,
+            },
+            "mesh" => {
+                let first_token = self.peek();
+                let first = self.word("mesh file name")?;
+                let (constants, filename) = if first.starts_with(':') {
+                    (None, first)
+                } else {
+                    self.check_defined(first_token, Name::Constants, &first)?;
+                    (Some(first), self.word("mesh file name")?)
+                };
+                Command::Mesh {
+                    constants,
+                    filename: filename.trim_start_matches(':').to_string(),
+                    coord: self.opt_name(Name::CoordSystem)?,
+                }
+            }
+
+            "line" => {
+                // lines aren't shaded, so constants are accepted but not used
+                let _constants = self.opt_name(Name::Constants)?;
+                Command::Line {
+                    p0: self.point()?,
+                    coord0: self.opt_name(Name::CoordSystem)?,
+                    p1: self.point()?,
+                    coord1: self.opt_name(Name::CoordSystem)?,
+                }
+            }
+            "circle" => Command::Circle {
+                center: self.point()?,
+                radius: self.number("radius")?,
+            },
+            "hermite" => Command::Hermite {
+                p0: self.point2()?,
+                p1: self.point2()?,
+                r0: self.point2()?,
+                r1: self.point2()?,
+            },
+            "bezier" => Command::Bezier {
+                p0: self.point2()?,
+                p1: self.point2()?,
+                p2: self.point2()?,
+                p3: self.point2()?,
+            },
+
+            "constants" => {
+                let name = self.word("constants name")?;
+                self.constants.insert(name.clone());
+                let mut k = [0.; 9];
+                for k in k.iter_mut() {
+                    *k = self.number("reflection constant")?;
+                }
+                let emissive = match self.peek().kind {
+                    TokenKind::Number(_) => Some(self.color()?),
+                    _ => None,
+                };
+                Command::Constants {
+                    name,
+                    material: Material {
+                        ambient: (k[0], k[3], k[6]),
+                        diffuse: (k[1], k[4], k[7]),
+                        specular: (k[2], k[5], k[8]),
+                        emissive,
+                        ..Default::default()
+                    },
+                }
+            }
+            "light" => Command::Light {
+                name: self.word("light name")?,
+                color: self.color()?,
+                location: self.point()?,
+            },
+            "point_light" => Command::PointLight {
+                name: self.word("light name")?,
+                color: self.color()?,
+                position: self.point()?,
+            },
+            "ambient" => Command::Ambient(self.color()?),
+            "shading" => {
+                let mode_token = self.peek();
+                Command::Shading(match self.word("shading mode")?.as_str() {
+                    "flat" => ShadingMode::Flat,
+                    "gouraud" => ShadingMode::Gouraud,
+                    "phong" => ShadingMode::Phong,
+                    _ => return self.error(mode_token, "shading mode (flat, gouraud or phong)"),
+                })
+            }
+            "fill" => {
+                let mode_token = self.peek();
+                Command::Fill(match self.word("fill mode")?.as_str() {
+                    "lit" => FillMode::Lit,
+                    "random" => FillMode::Random {
+                        seed: match self.peek().kind {
+                            TokenKind::Number(_) => self.uint("seed")? as u64,
+                            _ => 0,
+                        },
+                    },
+                    "fixed" => FillMode::Fixed(self.color()?),
+                    "palette" => {
+                        let mut colors = Vec::new();
+                        while let TokenKind::Number(_) = self.peek().kind {
+                            colors.push(self.color()?);
+                        }
+                        match FillMode::palette(colors) {
+                            Some(fill) => fill,
+                            None => return self.error(self.peek(), "palette colors (r g b)"),
+                        }
+                    }
+                    "normals" => FillMode::Normals,
+                    _ => {
+                        return self.error(
+                            mode_token,
+                            "fill mode (lit, random, fixed, palette or normals)",
+                        )
+                    }
+                })
+            }
+            "camera" => Command::Camera {
+                eye: self.point()?,
+                aim: self.point()?,
+                fov: match self.peek().kind {
+                    TokenKind::Number(_) => Some(self.number("field of view")?),
+                    _ => None,
+                },
+            },
+            "ortho" => Command::Ortho {
+                left: self.number("left")?,
+                right: self.number("right")?,
+                bottom: self.number("bottom")?,
+                top: self.number("top")?,
+                near: self.number("near")?,
+                far: self.number("far")?,
+            },
+
+            "size" => Command::Size {
+                width: self.size("width")?,
+                height: self.size("height")?,
+            },
+            "frames" => Command::Frames(self.uint("number of frames")?),
+            "basename" => Command::Basename(self.word("base name")?),
+            "vary" => Command::Vary {
+                knob: self.word("knob name")?,
+                start_frame: self.uint("start frame")?,
+                end_frame: self.uint("end frame")?,
+                start_val: self.number("start value")?,
+                end_val: self.number("end value")?,
+                easing: self.opt_easing()?,
+            },
+            "key" => Command::Key {
+                knob: self.word("knob name")?,
+                frame: self.uint("frame")?,
+                value: self.number("knob value")?,
+                easing: self.opt_easing()?,
+            },
+            "set" => Command::Set {
+                knob: self.word("knob name")?,
+                value: self.number("knob value")?,
+            },
+            "set_knobs" => Command::SetKnobs(self.number("knob value")?),
+
+            "display" => Command::Display,
+            "save" => Command::Save(self.word("file name")?),
+            "clear" => Command::Clear,
+            _ => unreachable!("{} is checked to be a command", token.text),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(src: &str, dialect: Dialect) -> Vec<Command> {
+        parse(src, "test", dialect)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.command)
+            .collect()
+    }
+
+    #[test]
+    fn mdl() {
+        let src = "// comment
+constants shiny 0.1 0.2 0.3 0.1 0.2 0.3 0.1 0.2 0.3
+push
+rotate x 45 knob
+sphere shiny 0 0 0 100
+mesh :file.obj
+save_coord_system c
+torus 0 0 0 20 100 c
+";
+        assert_eq!(
+            vec![
+                Command::Constants {
+                    name: String::from("shiny"),
+                    material: Material {
+                        ambient: (0.1, 0.1, 0.1),
+                        diffuse: (0.2, 0.2, 0.2),
+                        specular: (0.3, 0.3, 0.3),
+                        ..Default::default()
+                    }
+                },
+                Command::Push,
+                Command::Rotate {
+                    axis: Axis::X,
+                    degrees: 45.,
+                    knob: Some(String::from("knob"))
+                },
+                Command::Sphere {
+                    constants: Some(String::from("shiny")),
+                    center: (0., 0., 0.),
+                    radius: 100.,
+                    coord: None
+                },
+                Command::Mesh {
+                    constants: None,
+                    filename: String::from("file.obj"),
+                    coord: None
+                },
+                Command::SaveCoordSystem(String::from("c")),
+                Command::Torus {
+                    constants: None,
+                    center: (0., 0., 0.),
+                    radius1: 20.,
+                    radius2: 100.,
+                    coord: Some(String::from("c"))
+                },
+            ],
+            commands(src, Dialect::Mdl)
+        );
+    }
+
+    #[test]
+    fn solids() {
+        let src = "constants c 0 0 0 0 0 0 0 0 0
+save_coord_system s
+cylinder c 0 0 0 10 20
+cone 0 0 0 10 20
+frustum 0 0 0 10 5 20
+ellipsoid 0 0 0 1 2 3
+icosahedron c 1 2 3 4
+plane 0 0 0 100 50 4 2
+plane 0 0 0 100 50 s
+";
+        let commands = commands(src, Dialect::Mdl);
+        let c = Some(String::from("c"));
+        assert_eq!(
+            &[
+                Command::Cylinder {
+                    constants: c.clone(),
+                    base: (0., 0., 0.),
+                    radius: 10.,
+                    height: 20.,
+                    coord: None
+                },
+                Command::Cone {
+                    constants: None,
+                    base: (0., 0., 0.),
+                    radius_bottom: 10.,
+                    radius_top: 0.,
+                    height: 20.,
+                    coord: None
+                },
+                Command::Cone {
+                    constants: None,
+                    base: (0., 0., 0.),
+                    radius_bottom: 10.,
+                    radius_top: 5.,
+                    height: 20.,
+                    coord: None
+                },
+                Command::Ellipsoid {
+                    constants: None,
+                    center: (0., 0., 0.),
+                    radii: (1., 2., 3.),
+                    coord: None
+                },
+                Command::Polyhedron {
+                    constants: c,
+                    polyhedron: Polyhedron::Icosahedron,
+                    center: (1., 2., 3.),
+                    radius: 4.,
+                    coord: None
+                },
+                Command::Plane {
+                    constants: None,
+                    corner: (0., 0., 0.),
+                    width: 100.,
+                    height: 50.,
+                    divisions: (4, 2),
+                    coord: None
+                },
+                Command::Plane {
+                    constants: None,
+                    corner: (0., 0., 0.),
+                    width: 100.,
+                    height: 50.,
+                    divisions: (1, 1),
+                    coord: Some(String::from("s"))
+                },
+            ],
+            &commands[2..]
+        );
+
+        let e = parse("plane 0 0 0 100 50 4\npyramid 0 0 0 1", "test", Dialect::Mdl).unwrap_err();
+        let messages: Vec<&str> = e.0.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            vec![
+                "expected rows (a whole number larger than 0), found end of line",
+                "expected height, found end of line"
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn fill_modes() {
+        let src = "fill lit
+fill random
+fill random 7
+fill fixed 255 0 0
+fill palette 1 2 3 4 5 6
+";
+        let red = RGB::new(255, 0, 0);
+        assert_eq!(
+            vec![
+                Command::Fill(FillMode::Lit),
+                Command::Fill(FillMode::Random { seed: 0 }),
+                Command::Fill(FillMode::Random { seed: 7 }),
+                Command::Fill(FillMode::Fixed(red)),
+                Command::Fill(FillMode::Palette(vec![RGB::new(1, 2, 3), RGB::new(4, 5, 6)])),
+            ],
+            commands(src, Dialect::Mdl)
+        );
+
+        let src = "fill palette\nfill gray\nfill palette 1 2";
+        let e = parse(src, "test", Dialect::Mdl).unwrap_err();
+        let messages: Vec<&str> = e.0.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            vec![
+                "expected palette colors (r g b), found end of line",
+                "expected fill mode (lit, random, fixed, palette or normals), found \"gray\"",
+                "expected blue, found end of line"
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn legacy_matches_mdl() {
+        let legacy = "#comment\npush\nmove\n1 2 3\nrotate\ny 20\nbox\n0 0 0 1 2 3\nsave\nout.png";
+        let mdl = "push\nmove 1 2 3\nrotate y 20\nbox 0 0 0 1 2 3\nsave out.png";
+        assert_eq!(Dialect::Legacy, Dialect::detect(legacy));
+        assert_eq!(Dialect::Mdl, Dialect::detect(mdl));
+        assert_eq!(
+            commands(mdl, Dialect::Mdl),
+            commands(legacy, Dialect::Legacy)
+        );
+    }
+
+    #[test]
+    fn legacy_script_file() {
+        let src = std::fs::read_to_string("script").unwrap();
+        assert_eq!(Dialect::Legacy, Dialect::detect(&src));
+        assert!(parse(&src, "script", Dialect::Legacy).is_ok());
+    }
+
+    fn errors(src: &str, dialect: Dialect) -> Vec<(usize, usize, String)> {
+        parse(src, "test", dialect)
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|e| (e.line, e.column, e.token))
+            .collect()
+    }
+
+    #[test]
+    fn collects_all_errors() {
+        let src = "sphere 0 0 0
+rotate w 30
+bogus 1 2
+push 1
+box shiny 0 0 0 1 1 1
+move 1 2 3";
+        assert_eq!(
+            vec![
+                (1, 13, String::new()),
+                (2, 8, String::from("w")),
+                (3, 1, String::from("bogus")),
+                (4, 6, String::from("1")),
+                (5, 5, String::from("shiny")),
+            ],
+            errors(src, Dialect::Mdl)
+        );
+    }
+
+    #[test]
+    fn error_details() {
+        let e = parse("push\nsphere 0 0 zero 10", "a.mdl", Dialect::Mdl).unwrap_err();
+        let e = &e.0[0];
+        assert_eq!("a.mdl", e.file);
+        assert_eq!("sphere 0 0 zero 10", e.source_line);
+        assert_eq!(
+            Some("sphere [constants] x y z r [coord]"),
+            e.hint.as_deref()
+        );
+        assert_eq!("expected z, found \"zero\"", e.message);
+    }
+
+    #[test]
+    fn unbalanced_pop() {
+        let e = parse("push\npop\n  pop", "a.mdl", Dialect::Mdl).unwrap_err();
+        let e = &e.0[0];
+        assert_eq!((3, 3), (e.line, e.column));
+        assert_eq!("pop without matching push", e.message);
+        assert!(parse("push\npush\npop\npop", "a.mdl", Dialect::Mdl).is_ok());
+    }
+
+    #[test]
+    fn legacy_errors_use_argument_line() {
+        let src = "move\n1 2 x\nbogus\n1 2 3\npush";
+        assert_eq!(
+            vec![(2, 5, String::from("x")), (3, 1, String::from("bogus"))],
+            errors(src, Dialect::Legacy)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Splits script source into tokens
+
+/// Kinds of tokens in a script
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    /// Anything that isn't a number: commands, names, file paths
+    Word(String),
+    Number(f64),
+    /// End of a line; commands end at new lines
+    Newline,
+    Eof,
+}
+
+/// A token and where it starts in the source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Text of the token as written in the source
+    pub text: String,
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Column number, starting from 1
+    pub col: usize,
+}
+
+/// Returns true if the rest of the line (already trimmed) is a comment
+///
+/// Comments start with `//` or `#`.
+/// Lines starting with `\` are also treated as comments for old scripts.
+fn is_comment(rest: &str) -> bool {
+    rest.starts_with("//") || rest.starts_with('#') || rest.starts_with('\\')
+}
+
+/// Split `src` into tokens
+///
+/// The last token is always `TokenKind::Eof`.
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut line_count = 0;
+
+    for (i, line) in src.lines().enumerate() {
+        let line_num = i + 1;
+        line_count = line_num;
+
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if is_comment(&line[start..]) {
+                break;
+            }
+
+            // read until whitespace
+            let mut end = line.len();
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() {
+                    end = i;
+                    break;
+                }
+                chars.next();
+            }
+
+            let text = &line[start..end];
+            let kind = match text.parse::<f64>() {
+                Ok(n) if n.is_finite() => TokenKind::Number(n),
+                _ => TokenKind::Word(text.to_string()),
+            };
+            tokens.push(Token {
+                kind,
+                text: text.to_string(),
+                line: line_num,
+                col: line[..start].chars().count() + 1,
+            });
+        }
+
+        tokens.push(Token {
+            kind: TokenKind::Newline,
+            text: String::new(),
+            line: line_num,
+            col: line.chars().count() + 1,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        text: String::new(),
+        line: line_count + 1,
+        col: 1,
+    });
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        tokenize(src).into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn words_numbers_and_comments() {
+        use TokenKind::*;
+        assert_eq!(
+            vec![
+                Word("sphere".to_string()),
+                Number(0.),
+                Number(-1.5),
+                Number(100.),
+                Newline,
+                Newline,
+                Word("mesh".to_string()),
+                Word(":a.obj".to_string()),
+                Newline,
+                Eof
+            ],
+            kinds("sphere 0 -1.5 100 // comment\n# comment\n  mesh\t:a.obj")
+        );
+    }
+
+    #[test]
+    fn positions() {
+        let tokens = tokenize("push\n  move 1 2 3");
+        let mv = &tokens[2];
+        assert_eq!((2, 3), (mv.line, mv.col));
+        let three = &tokens[5];
+        assert_eq!(TokenKind::Number(3.), three.kind);
+        assert_eq!((2, 12), (three.line, three.col));
+    }
+}
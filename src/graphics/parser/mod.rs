@@ -0,0 +1,908 @@
+//! Goes through the file named filename and performs all of the actions listed in that file.
+//!
+//! Scripts are written in the MDL dialect, one command per line (see `grammar` for all commands):
+//!
+//! - push
+//!     - Push a copy of the current top of the coordinate system (cs) stack onto the cs stack (a full copy, not just a reference to the current top… I’m looking at you python people)
+//! - pop
+//!     - Removes the top of the cs stack (nothing needs to be done with this data)
+//! - move/rotate/scale
+//!     - create a translation/rotation/scale matrix
+//!     - multiply the current top of the cs stack by it
+//!     - The ordering of multiplication is important here. (see notes)
+//! - box/sphere/torus/ellipsoid/cylinder/cone/frustum/pyramid/tetrahedron/octahedron/icosahedron/plane/mesh
+//!     - add the solid, or the faces of an OBJ or STL file, to a temporary polygon matrix
+//!     - multiply it by the current top of the cs stack
+//!     - draw it to the screen
+//!     - clear the polygon matrix
+//! - line/curve/circle
+//!     - add a line to a temporary edge matrix
+//!     - multiply it by the current top
+//!     - draw it to the screen (note a line is not a solid, so avoid draw_polygons)
+//!     - clear the edge matrix
+//! - save
+//!     - save the screen with the provided file name
+//! - display
+//!     - show the image
+//! - size
+//!     - draw the script in a scene of the given size, scaled to fit the image
+//! - camera
+//!     - draw everything after it in perspective, seen from the eye looking at the aim point
+//! - ortho
+//!     - draw everything after it in world units, with the given box filling the image
+//! - frames/basename/vary/key
+//!     - make the script an animation: it runs once per frame, and each frame is saved in `anim/`
+//!     - knobs named after move/scale/rotate scale the transformation by their value in that frame
+//!
+//! Older scripts that put arguments on the line after their command still run in the legacy dialect,
+//! which is detected automatically.
+//!
+//! Also note that the ident and apply commands no longer have any use
+//!
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub mod animation;
+pub mod ast;
+pub mod error;
+pub mod grammar;
+pub mod lexer;
+
+use crate::graphics::{
+    camera::{Camera, Projection, DEFAULT_FOV},
+    drawer::Drawer,
+    gif::GifEncoder,
+    img::ImageFormat,
+    light::Light,
+    matrix::transform as tr,
+    svg::SvgCanvas,
+    terminal::DisplayMode,
+    vector::Vec3,
+    Canvas, PPMImg,
+};
+use animation::Animation;
+use ast::{Axis, Command, Statement};
+use error::{ParseError, ParseErrors};
+use grammar::Dialect;
+
+/// Size of scripts that don't have a `size` command
+pub const DEFAULT_SIZE: (usize, usize) = (500, 500);
+
+/// Something done while running a script, see `DWScript::on_progress`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    /// Frame `frame` out of `frames` of an animation was rendered, counting from 1
+    Frame { frame: usize, frames: usize },
+    /// The GIF of an animation was saved at this path
+    SavedGif(PathBuf),
+    /// The solids drawn were exported to `path`
+    Exported { triangles: usize, path: String },
+}
+
+pub struct DWScript {
+    filename: String,
+    drawer: Drawer,
+    knobs: HashMap<String, f64>,
+    /// Source of the script, kept to point at lines in runtime errors
+    src: String,
+    /// Directory animation frames are saved in
+    anim_dir: PathBuf,
+    /// Images shown by `display`, if they are kept instead of shown
+    displayed: Option<Vec<PPMImg>>,
+    /// Images written by `save` and the paths they were for, if they are kept instead of saved
+    saved: Option<Vec<(String, PPMImg)>>,
+    /// Ignore `display` commands
+    skip_displays: bool,
+    /// How `display` commands show the image
+    display_mode: DisplayMode,
+    /// Size of the image, overriding the size of the script
+    image_size: Option<(usize, usize)>,
+    /// First and last frame of animations to render, all of them if `None`
+    frame_range: Option<(usize, usize)>,
+    /// Format animation frames are saved in, `None` to only save the GIF
+    frame_format: Option<ImageFormat>,
+    /// Where to save the GIF of animations instead of `<basename>.gif` in the animation directory
+    gif_path: Option<PathBuf>,
+    /// Mesh files used by the script, found when it was last parsed
+    meshes: Vec<PathBuf>,
+    /// Where to save the solids drawn, as a 3d scene
+    export_path: Option<String>,
+    /// Draw on an `SvgCanvas` instead of a `PPMImg`
+    svg: bool,
+    /// Called with the progress of the script, if anyone is listening
+    progress: Option<Box<dyn FnMut(Progress)>>,
+}
+
+impl DWScript {
+    pub fn new(filename: &str) -> Self {
+        DWScript {
+            filename: filename.to_string(),
+            drawer: Drawer::new(Box::new(PPMImg::new(
+                DEFAULT_SIZE.1 as u32,
+                DEFAULT_SIZE.0 as u32,
+                255,
+            ))),
+            knobs: HashMap::new(),
+            src: String::new(),
+            anim_dir: PathBuf::from("anim"),
+            displayed: None,
+            saved: None,
+            skip_displays: false,
+            display_mode: DisplayMode::default(),
+            image_size: None,
+            frame_range: None,
+            frame_format: Some(ImageFormat::Ppm),
+            gif_path: None,
+            meshes: Vec::new(),
+            export_path: None,
+            svg: false,
+            progress: None,
+        }
+    }
+
+    /// Call `report` as the script runs, e.g. to print the progress of an animation
+    pub fn on_progress(&mut self, report: impl FnMut(Progress) + 'static) {
+        self.progress = Some(Box::new(report));
+    }
+
+    fn report(&mut self, progress: Progress) {
+        if let Some(report) = self.progress.as_mut() {
+            report(progress);
+        }
+    }
+
+    /// Render `width` by `height` images, scaling the scene of the script to fit
+    pub fn set_image_size(&mut self, width: usize, height: usize) {
+        self.image_size = Some((width, height));
+    }
+
+    /// Save animation frames in `dir` instead of `anim`
+    pub fn set_anim_dir(&mut self, dir: &str) {
+        self.anim_dir = PathBuf::from(dir);
+    }
+
+    /// Keep the images `display` would show instead of showing them, see `take_displayed`
+    #[cfg(test)]
+    pub fn capture_displays(&mut self) {
+        self.displayed.get_or_insert_with(Vec::new);
+    }
+
+    /// Keep the images `save` would write instead of writing them, see `take_saved`
+    #[cfg(test)]
+    pub fn capture_saves(&mut self) {
+        self.saved.get_or_insert_with(Vec::new);
+    }
+
+    /// Ignore `display` commands, e.g. when there is no screen to show images on
+    pub fn skip_displays(&mut self) {
+        self.skip_displays = true;
+    }
+
+    /// Show the images of `display` commands as `mode`, in a window by default
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        self.display_mode = mode;
+    }
+
+    /// Only render frames `first` to `last` of animations, counting from 0
+    pub fn set_frame_range(&mut self, first: usize, last: usize) {
+        self.frame_range = Some((first, last));
+    }
+
+    /// Save animation frames as `format` instead of PPM, or not at all if `None`
+    pub fn set_frame_format(&mut self, format: Option<ImageFormat>) {
+        self.frame_format = format;
+    }
+
+    /// Save the GIF of animations to `path` instead of the animation directory
+    pub fn set_gif_path(&mut self, path: &str) {
+        self.gif_path = Some(PathBuf::from(path));
+    }
+
+    /// Save the solids drawn by the script to `path`, as OBJ, PLY or STL from its extension
+    ///
+    /// For animations, that's the solids of the last frame rendered.
+    pub fn set_export_path(&mut self, path: &str) {
+        self.export_path = Some(path.to_string());
+    }
+
+    /// Draw shapes as SVG elements instead of pixels, to `save` the drawing as SVG
+    pub fn draw_svg(&mut self) {
+        self.svg = true;
+    }
+
+    /// Returns a copy of what has been drawn so far
+    pub fn image(&self) -> PPMImg {
+        self.drawer.to_ppm()
+    }
+
+    /// Save what has been drawn so far, as SVG if drawing SVG and `filepath` ends in `.svg`
+    pub fn save(&self, filepath: &str) -> io::Result<()> {
+        self.drawer.save(filepath)
+    }
+
+    /// Returns the images kept by `display` so far
+    #[cfg(test)]
+    pub fn take_displayed(&mut self) -> Vec<PPMImg> {
+        self.displayed
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Returns the images kept by `save` so far, with the paths they were for
+    #[cfg(test)]
+    pub fn take_saved(&mut self) -> Vec<(String, PPMImg)> {
+        self.saved.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Read and parse the script into statements
+    pub fn parse(&mut self) -> Result<Vec<Statement>, ParseErrors> {
+        self.src = fs::read_to_string(&self.filename).map_err(|e| {
+            ParseErrors(vec![ParseError::file_error(
+                &self.filename,
+                format!("couldn't read script: {}", e),
+            )])
+        })?;
+        let statements =
+            grammar::parse(&self.src, &self.filename, Dialect::detect(&self.src))?;
+        self.meshes = statements
+            .iter()
+            .filter_map(|s| match &s.command {
+                Command::Mesh { filename, .. } => Some(PathBuf::from(filename)),
+                _ => None,
+            })
+            .collect();
+        Ok(statements)
+    }
+
+    /// Returns the files the script is made of: the script itself,
+    /// and the meshes it used when it was last parsed
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = vec![PathBuf::from(&self.filename)];
+        for mesh in self.meshes.iter() {
+            if !files.contains(mesh) {
+                files.push(mesh.clone());
+            }
+        }
+        files
+    }
+
+    /// Parse the script and work out its animation without drawing anything,
+    /// returning the same errors `do_parse` would find before running
+    pub fn check(&mut self) -> Result<Option<Animation>, ParseErrors> {
+        let statements = self.parse()?;
+        Animation::new(&statements).map_err(|(statement, message)| {
+            ParseErrors(vec![self.runtime_error(statement, message)])
+        })
+    }
+
+    /// Parse and run the script, returning every error found while parsing
+    /// or the first error while running
+    ///
+    /// Animated scripts are run once per frame, see `run_animation`, and their animation is returned.
+    pub fn do_parse(&mut self) -> Result<Option<Animation>, ParseErrors> {
+        let statements = self.parse()?;
+        let animation = Animation::new(&statements).map_err(|(statement, message)| {
+            ParseErrors(vec![self.runtime_error(statement, message)])
+        })?;
+        self.set_up_canvas(&statements);
+        match &animation {
+            Some(animation) => self.run_animation(&statements, animation)?,
+            None => self.run(&statements)?,
+        }
+        self.export()?;
+        Ok(animation)
+    }
+
+    /// Make a new canvas the size of the image, fitting the scene of the script
+    ///
+    /// The scene is the size given by the last `size` command, or `DEFAULT_SIZE`.
+    /// Without an image size, the image is as large as the scene.
+    fn set_up_canvas(&mut self, statements: &[Statement]) {
+        let scene = statements
+            .iter()
+            .rev()
+            .find_map(|s| match s.command {
+                Command::Size { width, height } => Some((width, height)),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_SIZE);
+        let (width, height) = self.image_size.unwrap_or(scene);
+        let canvas: Box<dyn Canvas> = if self.svg {
+            Box::new(SvgCanvas::new(width as u32, height as u32))
+        } else {
+            Box::new(PPMImg::new(height as u32, width as u32, 255))
+        };
+        self.drawer = Drawer::new(canvas);
+        self.drawer.set_scene_size(scene.0 as f64, scene.1 as f64);
+        self.drawer.set_display_mode(self.display_mode);
+        if self.export_path.is_some() {
+            self.drawer.start_recording();
+        }
+    }
+
+    /// Render every frame of `animation` and save them in the animation directory,
+    /// along with `<basename>.gif` made of all the frames
+    ///
+    /// Each frame starts from a cleared screen and a fresh coordinate stack,
+    /// with the knobs set to their values for that frame.
+    pub fn run_animation(
+        &mut self,
+        statements: &[Statement],
+        animation: &Animation,
+    ) -> Result<(), ParseErrors> {
+        let (first, last) = self
+            .frame_range
+            .unwrap_or((0, animation.frames.saturating_sub(1)));
+        if first > last || last >= animation.frames {
+            return Err(ParseErrors(vec![ParseError::file_error(
+                &self.filename,
+                format!(
+                    "can't render frames {} to {}, the animation has frames 1 to {}",
+                    first + 1,
+                    last + 1,
+                    animation.frames
+                ),
+            )]));
+        }
+
+        fs::create_dir_all(&self.anim_dir).map_err(|e| self.save_error(&self.anim_dir, e))?;
+        let gif_path = self
+            .gif_path
+            .clone()
+            .unwrap_or_else(|| self.anim_dir.join(format!("{}.gif", animation.basename)));
+        let mut gif = None;
+
+        for frame in first..=last {
+            self.knobs = animation.knobs[frame].clone();
+            self.drawer.clear();
+            self.drawer.reset_stack();
+            self.drawer.remove_camera();
+            if self.export_path.is_some() {
+                self.drawer.start_recording();
+            }
+            self.run(statements)?;
+
+            let img = self.drawer.to_ppm();
+            if let Some(format) = self.frame_format.filter(|&f| f != ImageFormat::Gif) {
+                let path = self.anim_dir.join(format!(
+                    "{}.{}",
+                    animation.frame_name(frame),
+                    format.extension()
+                ));
+                img.save_as(&path.to_string_lossy(), format)
+                    .map_err(|e| self.save_error(&path, e))?;
+            }
+            self.report(Progress::Frame {
+                frame: frame + 1,
+                frames: animation.frames,
+            });
+
+            if gif.is_none() {
+                let mut encoder =
+                    GifEncoder::create(&gif_path.to_string_lossy(), img.width(), img.height())
+                        .map_err(|e| self.save_error(&gif_path, e))?;
+                encoder.set_diff_transparency(true);
+                gif = Some(encoder);
+            }
+            if let Some(gif) = gif.as_mut() {
+                gif.add_frame(&img)
+                    .map_err(|e| self.save_error(&gif_path, e))?;
+            }
+        }
+
+        if let Some(gif) = gif {
+            gif.finish().map_err(|e| self.save_error(&gif_path, e))?;
+            self.report(Progress::SavedGif(gif_path));
+        }
+        Ok(())
+    }
+
+    /// Returns the value of `knob`, or 1 if there is no knob
+    ///
+    /// Knobs that have never been set are also 1.
+    fn knob_value(&self, knob: &Option<String>) -> f64 {
+        knob.as_ref()
+            .and_then(|k| self.knobs.get(k))
+            .copied()
+            .unwrap_or(1.)
+    }
+
+    /// Execute `statements` on the drawer
+    ///
+    /// `frames`, `basename`, `vary` and `key` are read by `Animation` before running and are skipped here,
+    /// like `size`, which is read by `do_parse`.
+    ///
+    /// Stops at the first command that fails, like saving to a bad path.
+    pub fn run(&mut self, statements: &[Statement]) -> Result<(), ParseErrors> {
+        for statement in statements {
+            self.execute(&statement.command)
+                .map_err(|message| ParseErrors(vec![self.runtime_error(statement, message)]))?;
+        }
+        Ok(())
+    }
+
+    /// Save what the drawer recorded to the export path, if there is one
+    fn export(&mut self) -> Result<(), ParseErrors> {
+        let (path, recording) = match (self.export_path.clone(), self.drawer.stop_recording()) {
+            (Some(path), Some(recording)) => (path, recording),
+            _ => return Ok(()),
+        };
+        recording.save(&path).map_err(|e| {
+            ParseErrors(vec![ParseError::file_error(
+                &path,
+                format!("couldn't export scene: {}", e),
+            )])
+        })?;
+        self.report(Progress::Exported {
+            triangles: recording.triangle_count(),
+            path,
+        });
+        Ok(())
+    }
+
+    fn save_error(&self, path: &Path, e: io::Error) -> ParseErrors {
+        ParseErrors(vec![ParseError::file_error(
+            &path.to_string_lossy(),
+            format!("couldn't save animation: {}", e),
+        )])
+    }
+
+    /// An error from running `statement`, pointing at its command
+    fn runtime_error(&self, statement: &Statement, message: String) -> ParseError {
+        let source_line = self
+            .src
+            .lines()
+            .nth(statement.line.wrapping_sub(1))
+            .unwrap_or("");
+        let trimmed = source_line.trim_start();
+        ParseError {
+            file: self.filename.clone(),
+            line: statement.line,
+            column: source_line.chars().count() - trimmed.chars().count() + 1,
+            token: trimmed.split_whitespace().next().unwrap_or("").to_string(),
+            message,
+            hint: None,
+            source_line: source_line.to_string(),
+        }
+    }
+
+    fn execute(&mut self, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Push => self.drawer.push_matrix(),
+            Command::Pop => self.drawer.pop_matrix().map_err(|e| e.to_string())?,
+            Command::Move { delta, knob } => {
+                let k = self.knob_value(knob);
+                self.drawer
+                    .transform_by(&tr::mv(delta.0 * k, delta.1 * k, delta.2 * k));
+            }
+            Command::Scale { factors, knob } => {
+                let k = self.knob_value(knob);
+                self.drawer
+                    .transform_by(&tr::scale(factors.0 * k, factors.1 * k, factors.2 * k));
+            }
+            Command::Rotate {
+                axis,
+                degrees,
+                knob,
+            } => {
+                let deg = degrees * self.knob_value(knob);
+                self.drawer.transform_by(&match axis {
+                    Axis::X => tr::rotatex(deg),
+                    Axis::Y => tr::rotatey(deg),
+                    Axis::Z => tr::rotatez(deg),
+                });
+            }
+            Command::SaveCoordSystem(name) => self.drawer.save_coord_system(name),
+
+            Command::Box {
+                constants,
+                corner,
+                dx,
+                dy,
+                dz,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_box(*corner, *dx, *dy, *dz, constants.as_deref())
+            })?,
+            Command::Sphere {
+                constants,
+                center,
+                radius,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_sphere(*center, *radius, constants.as_deref())
+            })?,
+            Command::Torus {
+                constants,
+                center,
+                radius1,
+                radius2,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_torus(*center, *radius1, *radius2, constants.as_deref())
+            })?,
+            Command::Ellipsoid {
+                constants,
+                center,
+                radii,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_ellipsoid(*center, *radii, constants.as_deref())
+            })?,
+            Command::Cylinder {
+                constants,
+                base,
+                radius,
+                height,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_cylinder(*base, *radius, *height, constants.as_deref())
+            })?,
+            Command::Cone {
+                constants,
+                base,
+                radius_bottom,
+                radius_top,
+                height,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_frustum(
+                    *base,
+                    *radius_bottom,
+                    *radius_top,
+                    *height,
+                    constants.as_deref(),
+                )
+            })?,
+            Command::Pyramid {
+                constants,
+                base,
+                side,
+                height,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_pyramid(*base, *side, *height, constants.as_deref())
+            })?,
+            Command::Polyhedron {
+                constants,
+                polyhedron,
+                center,
+                radius,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_polyhedron(*polyhedron, *center, *radius, constants.as_deref())
+            })?,
+            Command::Plane {
+                constants,
+                corner,
+                width,
+                height,
+                divisions,
+                coord,
+            } => self.with_coord(coord, |d| {
+                d.add_plane(*corner, *width, *height, *divisions, constants.as_deref())
+            })?,
+            Command::Mesh {
+                constants,
+                filename,
+                coord,
+            } => self
+                .with_coord(coord, |d| {
+                    d.add_mesh(filename, constants.as_deref())
+                        .map_err(|e| format!("couldn't load mesh {}: {}", filename, e))
+                })?,
+
+            Command::Line {
+                p0,
+                coord0,
+                p1,
+                coord1,
+            } => self
+                .drawer
+                .draw_line_across(*p0, coord0.as_deref(), *p1, coord1.as_deref())
+                .map_err(|e| e.to_string())?,
+            Command::Circle { center, radius } => self.drawer.draw_circle(*center, *radius),
+            Command::Hermite { p0, p1, r0, r1 } => self.drawer.draw_hermite(*p0, *p1, *r0, *r1),
+            Command::Bezier { p0, p1, p2, p3 } => self.drawer.draw_bezier(*p0, *p1, *p2, *p3),
+
+            Command::Constants { name, material } => self.drawer.add_material(name, *material),
+            Command::Light {
+                name,
+                color,
+                location,
+            } => self.drawer.add_light(
+                name,
+                Light::Directional {
+                    dir: Vec3::from_pt(*location),
+                    color: *color,
+                },
+            ),
+            Command::PointLight {
+                name,
+                color,
+                position,
+            } => self.drawer.add_light(
+                name,
+                Light::Point {
+                    pos: Vec3::from_pt(*position),
+                    color: *color,
+                },
+            ),
+            Command::Ambient(color) => self.drawer.set_ambient(*color),
+            Command::Shading(mode) => self.drawer.set_shading(*mode),
+            Command::Fill(fill) => self.drawer.set_fill_mode(fill.clone()),
+            Command::Camera { eye, aim, fov } => {
+                let mut camera = Camera::new(Vec3::from_pt(*eye), Vec3::from_pt(*aim));
+                camera.projection = Projection::Perspective {
+                    fov: fov.unwrap_or(DEFAULT_FOV),
+                    aspect: self.drawer.aspect(),
+                };
+                self.drawer.set_camera(camera);
+            }
+            Command::Ortho {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            } => self.drawer.set_camera(Camera::orthographic(
+                *left, *right, *bottom, *top, *near, *far,
+            )),
+
+            Command::Size { .. }
+            | Command::Frames(_)
+            | Command::Basename(_)
+            | Command::Vary { .. }
+            | Command::Key { .. } => {}
+            Command::Set { knob, value } => {
+                self.knobs.insert(knob.clone(), *value);
+            }
+            Command::SetKnobs(value) => {
+                for v in self.knobs.values_mut() {
+                    *v = *value;
+                }
+            }
+
+            Command::Display if self.skip_displays => {}
+            Command::Display => match self.displayed.as_mut() {
+                Some(displayed) => displayed.push(self.drawer.to_ppm()),
+                None => self.drawer.display(),
+            },
+            Command::Save(filename) => match self.saved.as_mut() {
+                Some(saved) => saved.push((filename.clone(), self.drawer.to_ppm())),
+                None => self
+                    .drawer
+                    .save(filename)
+                    .map_err(|e| format!("couldn't save {}: {}", filename, e))?,
+            },
+            Command::Clear => self.drawer.clear(),
+        }
+        Ok(())
+    }
+
+    /// Draw with `draw` in the coordinate system named `coord`, or the top of the stack if `None`
+    fn with_coord<R, E: fmt::Display>(
+        &mut self,
+        coord: &Option<String>,
+        draw: impl FnOnce(&mut Drawer) -> Result<R, E>,
+    ) -> Result<R, String> {
+        let result = match coord {
+            Some(name) => self
+                .drawer
+                .with_coord_system(name, draw)
+                .map_err(|e| e.to_string())?,
+            None => draw(&mut self.drawer),
+        };
+        result.map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::golden::assert_golden;
+    #[test]
+    fn script() {
+        let mut s = DWScript::new("script");
+        s.capture_displays();
+        s.capture_saves();
+        s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+        let displayed = s.take_displayed();
+        assert_eq!(6, displayed.len());
+        for (i, img) in displayed.iter().enumerate() {
+            assert_golden(&format!("script_{}", i), img);
+        }
+        let saved = s.take_saved();
+        assert_eq!(1, saved.len());
+        assert_eq!("robot.png", saved[0].0);
+        assert_eq!(displayed[5].pixels(), saved[0].1.pixels());
+    }
+
+    #[test]
+    fn animation_frames() {
+        let dir = std::env::temp_dir().join("w8_solids_animation_frames");
+        let script = dir.join("spin.mdl");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &script,
+            "frames 3
+basename spin
+vary turn 0 2 0 1
+move 250 250 0
+rotate z 90 turn
+box -50 -50 -50 100 40 100",
+        )
+        .unwrap();
+
+        let mut s = DWScript::new(&script.to_string_lossy());
+        s.set_anim_dir(&dir.to_string_lossy());
+        s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+
+        let frames: Vec<Vec<u8>> = (0..3)
+            .map(|i| fs::read(dir.join(format!("spin00{}.ppm", i))).unwrap())
+            .collect();
+        assert_ne!(frames[0], frames[1]);
+        assert_ne!(frames[1], frames[2]);
+        let gif = fs::read(dir.join("spin.gif")).unwrap();
+        assert_eq!(b"GIF89a", &gif[..6]);
+
+        // only the last two frames, as PNG, with the GIF somewhere else
+        let frames_dir = dir.join("png");
+        let gif_path = dir.join("out.gif");
+        let mut s = DWScript::new(&script.to_string_lossy());
+        s.set_anim_dir(&frames_dir.to_string_lossy());
+        s.set_frame_range(1, 2);
+        s.set_frame_format(Some(ImageFormat::Png));
+        s.set_gif_path(&gif_path.to_string_lossy());
+        let progress = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let reported = progress.clone();
+        s.on_progress(move |p| reported.borrow_mut().push(p));
+        s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(
+            vec![
+                Progress::Frame {
+                    frame: 2,
+                    frames: 3
+                },
+                Progress::Frame {
+                    frame: 3,
+                    frames: 3
+                },
+                Progress::SavedGif(gif_path.clone()),
+            ],
+            *progress.borrow()
+        );
+        assert!(!frames_dir.join("spin000.png").exists());
+        assert!(frames_dir.join("spin001.png").exists());
+        assert!(frames_dir.join("spin002.png").exists());
+        assert!(!frames_dir.join("spin.gif").exists());
+        assert!(gif_path.exists());
+
+        let mut s = DWScript::new(&script.to_string_lossy());
+        s.set_frame_range(1, 3);
+        let e = s.do_parse().unwrap_err();
+        assert!(e.to_string().contains("frames 2 to 4"), "{}", e);
+        assert_eq!(3, s.check().unwrap().unwrap().frames);
+        assert_eq!(vec![script.clone()], s.files());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mesh() {
+        let dir = std::env::temp_dir().join("w8_solids_mesh");
+        let (script, mesh) = (dir.join("mesh.mdl"), dir.join("square.obj"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&mesh, "v 0 0 0\nv 100 0 0\nv 100 100 0\nv 0 100 0\nf 1 2 3 4").unwrap();
+        fs::write(
+            &script,
+            format!("move 50 50 0\nmesh :{}\ndisplay", mesh.display()),
+        )
+        .unwrap();
+
+        let mut s = DWScript::new(&script.to_string_lossy());
+        s.capture_displays();
+        s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(vec![script.clone(), mesh.clone()], s.files());
+        let img = s.take_displayed().remove(0);
+        // rows of the image go from the top
+        let pixel = |x: u32, y: u32| {
+            img.pixels()[((img.height() - 1 - y) * img.width() + x) as usize]
+        };
+        assert_ne!(pixel(0, 0), pixel(100, 100));
+        assert_eq!(pixel(0, 0), pixel(160, 100));
+
+        fs::remove_file(&mesh).unwrap();
+        let e = DWScript::new(&script.to_string_lossy()).do_parse().unwrap_err();
+        assert!(e.to_string().contains("couldn't load mesh"), "{}", e);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn export() {
+        let dir = std::env::temp_dir().join("w8_solids_export");
+        let (script, scene) = (dir.join("export.mdl"), dir.join("scene.ply"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &script,
+            "constants red 0.2 0.8 0 0 0 0 0 0 0\nmove 10 0 0\nbox red 0 1 0 1 1 1\nline 0 0 0 1 1 1",
+        )
+        .unwrap();
+
+        let mut s = DWScript::new(&script.to_string_lossy());
+        s.set_export_path(&scene.to_string_lossy());
+        s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+        let ply = fs::read_to_string(&scene).unwrap();
+        // the box, moved, and not the line
+        assert!(ply.contains("element face 12\n"), "{}", ply);
+        assert!(ply.contains("\n10 1 0\n"), "{}", ply);
+        assert!(ply.contains(" 255 0 0\n"), "{}", ply);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn svg() {
+        let dir = std::env::temp_dir().join("w8_solids_svg");
+        let (script, svg) = (dir.join("svg.mdl"), dir.join("svg.svg"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&script, "size 100 50\nline 0 0 0 99 49 0\nbox 10 40 0 20 20 20").unwrap();
+
+        let mut s = DWScript::new(&script.to_string_lossy());
+        s.draw_svg();
+        s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+        s.save(&svg.to_string_lossy()).unwrap();
+        let svg = fs::read_to_string(&svg).unwrap();
+        assert!(svg.contains(r#"width="100" height="50""#), "{}", svg);
+        assert!(svg.contains("<line "), "{}", svg);
+        // only the front of the box faces the viewer
+        assert_eq!(2, svg.matches("<polygon ").count(), "{}", svg);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn image_size() {
+        let dir = std::env::temp_dir().join("w8_solids_image_size");
+        let script = dir.join("size.mdl");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&script, "size 100 50\nbox 0 50 0 50 50 50\ndisplay").unwrap();
+
+        let render = |size: Option<(usize, usize)>| {
+            let mut s = DWScript::new(&script.to_string_lossy());
+            s.capture_displays();
+            if let Some((width, height)) = size {
+                s.set_image_size(width, height);
+            }
+            s.do_parse().unwrap_or_else(|e| panic!("{}", e));
+            s.take_displayed().remove(0)
+        };
+        let covered = |img: &PPMImg| {
+            let bg = img.pixels()[img.pixels().len() - 1];
+            let row = |y: u32| -> Vec<bool> {
+                (0..img.width())
+                    .map(|x| img.pixels()[(y * img.width() + x) as usize] != bg)
+                    .collect()
+            };
+            (img.width(), img.height(), row(img.height() / 2))
+        };
+
+        // the box fills the left half of the scene
+        let (width, height, row) = covered(&render(None));
+        assert_eq!((100, 50), (width, height));
+        assert_eq!(50, row.iter().filter(|&&c| c).count());
+        assert!(row[0] && row[49] && !row[50]);
+
+        let (width, height, row) = covered(&render(Some((400, 200))));
+        assert_eq!((400, 200), (width, height));
+        assert!(row[0] && row[199] && !row[200]);
+
+        // taller image, the scene is centered vertically
+        let (_, height, row) = covered(&render(Some((200, 200))));
+        assert_eq!(200, height);
+        assert!(row[0] && row[99] && !row[100]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
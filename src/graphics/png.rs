@@ -0,0 +1,578 @@
+//! PNG reading and writing without imagemagick
+//!
+//! Images are written as 8-bit RGB, or 16-bit if the depth of the `PPMImg` needs it.
+//! Any standard PNG can be read; transparency is ignored.
+
+use crate::graphics::{zlib, Canvas, PPMImg, RGB};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// (x start, y start, x step, y step) of the passes of an interlaced image
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("invalid PNG: {}", msg))
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    !bytes.iter().fold(!0u32, |c, &b| {
+        table[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8)
+    })
+}
+
+fn write_chunk(writer: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc_data = kind.to_vec();
+    crc_data.extend(data);
+    writer.write_all(&crc_data)?;
+    writer.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Filter `row` with `filter`, given the row above and the bytes per pixel
+fn filter_row(filter: u8, row: &[u8], prev: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    out.push(filter);
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let c = if i >= bpp { prev[i - bpp] } else { 0 };
+        let b = prev[i];
+        out.push(row[i].wrapping_sub(match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        }));
+    }
+}
+
+/// Undo `filter` on `row` in place
+fn unfilter_row(filter: u8, row: &mut [u8], prev: &[u8], bpp: usize) -> io::Result<()> {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let c = if i >= bpp { prev[i - bpp] } else { 0 };
+        let b = prev[i];
+        row[i] = row[i].wrapping_add(match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            4 => paeth(a, b, c),
+            _ => return Err(invalid(format!("unknown filter type {}", filter))),
+        });
+    }
+    Ok(())
+}
+
+/// Write `img` as a PNG
+///
+/// Each row uses the filter that gives the smallest sum of absolute differences,
+/// which usually compresses best.
+pub fn write_png(img: &PPMImg, writer: &mut dyn Write) -> io::Result<()> {
+    let (width, height, depth) = (img.width(), img.height(), img.depth() as u32);
+    let sixteen = depth > 255;
+    let max = if sixteen { 65535 } else { 255 };
+    let scale = |c: u16| ((c as u32).min(depth) * max + depth / 2) / depth;
+
+    let bpp = if sixteen { 6 } else { 3 };
+    let row_len = width as usize * bpp;
+    let mut rows = Vec::with_capacity(height as usize * row_len);
+    for c in img.pixels() {
+        for &channel in [c.red, c.green, c.blue].iter() {
+            if sixteen {
+                rows.extend(&(scale(channel) as u16).to_be_bytes());
+            } else {
+                rows.push(scale(channel) as u8);
+            }
+        }
+    }
+
+    let mut filtered = Vec::with_capacity(rows.len() + height as usize);
+    let zeros = vec![0; row_len];
+    let mut candidate = Vec::with_capacity(row_len + 1);
+    let mut best = Vec::with_capacity(row_len + 1);
+    for (y, row) in rows.chunks(row_len.max(1)).enumerate() {
+        let prev = if y == 0 {
+            &zeros[..]
+        } else {
+            &rows[(y - 1) * row_len..y * row_len]
+        };
+        let mut best_cost = u64::MAX;
+        for filter in 0..5 {
+            candidate.clear();
+            filter_row(filter, row, prev, bpp, &mut candidate);
+            let cost = candidate[1..]
+                .iter()
+                .map(|&b| (b as i8).unsigned_abs() as u64)
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        filtered.extend(&best);
+    }
+
+    let mut header = vec![];
+    header.extend(&width.to_be_bytes());
+    header.extend(&height.to_be_bytes());
+    // bit depth, truecolor, deflate, adaptive filtering, not interlaced
+    header.extend(&[if sixteen { 16 } else { 8 }, 2, 0, 0, 0]);
+
+    writer.write_all(&SIGNATURE)?;
+    write_chunk(writer, b"IHDR", &header)?;
+    write_chunk(writer, b"IDAT", &zlib::compress(&filtered))?;
+    write_chunk(writer, b"IEND", &[])?;
+    writer.flush()
+}
+
+/// Save `img` as a PNG at `filepath`
+pub fn save(img: &PPMImg, filepath: &str) -> io::Result<()> {
+    write_png(img, &mut BufWriter::new(File::create(filepath)?))
+}
+
+/// Image header
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            2 => 3,
+            4 => 2,
+            _ => 4,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// Bytes of a row `width` pixels wide, without the filter byte
+    fn row_len(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Width, height and position of the first pixel of each pass, and the steps between pixels
+    fn passes(&self) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+        let passes = if self.interlaced {
+            ADAM7.to_vec()
+        } else {
+            vec![(0, 0, 1, 1)]
+        };
+        passes
+            .into_iter()
+            // passes without pixels are left out of the data
+            .filter(|&(x0, y0, _, _)| x0 < self.width && y0 < self.height)
+            .map(|(x0, y0, dx, dy)| {
+                let width = (self.width - x0).div_ceil(dx);
+                let height = (self.height - y0).div_ceil(dy);
+                (width, height, x0, y0, dx, dy)
+            })
+            .collect()
+    }
+
+    /// Length of the inflated image data: every row of every pass with its filter byte,
+    /// or `None` if that doesn't fit in memory
+    fn data_len(&self) -> Option<usize> {
+        self.passes()
+            .into_iter()
+            .try_fold(0usize, |len, (width, height, ..)| {
+                let row_len = width.checked_mul(self.bits_per_pixel())?.div_ceil(8);
+                len.checked_add(row_len.checked_add(1)?.checked_mul(height)?)
+            })
+    }
+}
+
+/// Read a PNG into a PPMImg
+///
+/// Images with 16-bit channels have a depth of 65535, the rest 255.
+pub fn read_png(reader: &mut dyn Read) -> io::Result<PPMImg> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(invalid(String::from("not a PNG file")));
+    }
+
+    let mut header = None;
+    let mut palette = vec![];
+    let mut compressed = vec![];
+    loop {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > i32::MAX as usize {
+            return Err(invalid(String::from("chunk is too long")));
+        }
+        // only as much as the file really has, whatever the length says
+        let mut chunk = vec![];
+        (&mut *reader).take(len as u64 + 4).read_to_end(&mut chunk)?;
+        if chunk.len() != len + 4 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "invalid PNG: chunk is cut short",
+            ));
+        }
+        let mut crc = [0; 4];
+        reader.read_exact(&mut crc)?;
+        let kind = String::from_utf8_lossy(&chunk[..4]).into_owned();
+        if u32::from_be_bytes(crc) != crc32(&chunk) {
+            return Err(invalid(format!("{} chunk is corrupted", kind)));
+        }
+        let data = &chunk[4..];
+
+        match &chunk[..4] {
+            b"IHDR" => {
+                if data.len() != 13 {
+                    return Err(invalid(String::from("bad IHDR chunk")));
+                }
+                let u32_at = |i: usize| {
+                    u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize
+                };
+                let h = Header {
+                    width: u32_at(0),
+                    height: u32_at(4),
+                    bit_depth: data[8],
+                    color_type: data[9],
+                    interlaced: data[12] == 1,
+                };
+                let valid_depths: &[u8] = match h.color_type {
+                    0 => &[1, 2, 4, 8, 16],
+                    3 => &[1, 2, 4, 8],
+                    2 | 4 | 6 => &[8, 16],
+                    _ => &[],
+                };
+                if !valid_depths.contains(&h.bit_depth) {
+                    return Err(invalid(format!(
+                        "unsupported color type {} with bit depth {}",
+                        h.color_type, h.bit_depth
+                    )));
+                }
+                if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+                    return Err(invalid(String::from(
+                        "unknown compression, filter or interlace method",
+                    )));
+                }
+                if h.width == 0 || h.height == 0 {
+                    return Err(invalid(String::from("image has no pixels")));
+                }
+                if h.width > i32::MAX as usize || h.height > i32::MAX as usize {
+                    return Err(invalid(format!(
+                        "image is too large: {}x{}",
+                        h.width, h.height
+                    )));
+                }
+                header = Some(h);
+            }
+            b"PLTE" => palette = data.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            b"IDAT" => compressed.extend(data),
+            b"IEND" => break,
+            kind if kind[0].is_ascii_uppercase() => {
+                return Err(invalid(format!(
+                    "unknown critical chunk {}",
+                    String::from_utf8_lossy(kind)
+                )))
+            }
+            _ => {}
+        }
+    }
+
+    let h = header.ok_or_else(|| invalid(String::from("missing IHDR chunk")))?;
+    if h.color_type == 3 && palette.is_empty() {
+        return Err(invalid(String::from("missing palette")));
+    }
+    // the header alone doesn't say the image is really that large, the data has to match it
+    let expected = h.data_len().ok_or_else(|| {
+        invalid(format!("image is too large: {}x{}", h.width, h.height))
+    })?;
+    let data = zlib::decompress(&compressed, expected)?;
+    if data.len() != expected {
+        return Err(invalid(format!(
+            "image data should be {} bytes for a {}x{} image, found {}",
+            expected,
+            h.width,
+            h.height,
+            data.len()
+        )));
+    }
+
+    let bpp = (h.bits_per_pixel() / 8).max(1);
+    let depth: u16 = if h.bit_depth == 16 { 65535 } else { 255 };
+    let mut pixels = vec![RGB::gray(0); h.width * h.height];
+    let mut pos = 0;
+    for (pass_width, pass_height, x0, y0, dx, dy) in h.passes() {
+        let row_len = h.row_len(pass_width);
+        let mut prev = vec![0; row_len];
+
+        for row in 0..pass_height {
+            let end = pos + 1 + row_len;
+            let (filter, mut line) = match data.get(pos..end) {
+                Some(d) => (d[0], d[1..].to_vec()),
+                None => return Err(invalid(String::from("image data is too short"))),
+            };
+            unfilter_row(filter, &mut line, &prev, bpp)?;
+            pos = end;
+
+            let y = y0 + row * dy;
+            for col in 0..pass_width {
+                let x = x0 + col * dx;
+                pixels[y * h.width + x] = pixel(&h, &line, col, &palette)?;
+            }
+            prev = line;
+        }
+    }
+
+    Ok(PPMImg::from_pixels(
+        h.height as u32,
+        h.width as u32,
+        depth,
+        pixels,
+    ))
+}
+
+/// Color of pixel `i` in an unfiltered row
+fn pixel(h: &Header, row: &[u8], i: usize, palette: &[[u8; 3]]) -> io::Result<RGB> {
+    // sample `n` of the pixel
+    let sample = |n: usize| -> u16 {
+        let index = i * h.channels() + n;
+        match h.bit_depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            bits => {
+                let bits = bits as usize;
+                let byte = row[index * bits / 8];
+                let shift = 8 - bits - (index * bits) % 8;
+                ((byte >> shift) as u16) & ((1 << bits) - 1)
+            }
+        }
+    };
+    // scale samples narrower than a byte up to 255
+    let gray = |v: u16| match h.bit_depth {
+        1 => v * 255,
+        2 => v * 85,
+        4 => v * 17,
+        _ => v,
+    };
+
+    Ok(match h.color_type {
+        0 | 4 => RGB::gray(gray(sample(0))),
+        3 => {
+            let entry = sample(0) as usize;
+            let [r, g, b] = *palette
+                .get(entry)
+                .ok_or_else(|| invalid(format!("color {} is not in the palette", entry)))?;
+            RGB::new(r as u16, g as u16, b as u16)
+        }
+        _ => RGB::new(sample(0), sample(1), sample(2)),
+    })
+}
+
+/// Load the PNG at `filepath`
+pub fn load(filepath: &str) -> io::Result<PPMImg> {
+    read_png(&mut BufReader::new(File::open(filepath)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(img: &PPMImg) -> PPMImg {
+        let mut bytes = vec![];
+        write_png(img, &mut bytes).unwrap();
+        read_png(&mut &bytes[..]).unwrap()
+    }
+
+    fn gradient(depth: u16) -> PPMImg {
+        let (h, w) = (30, 40);
+        let pixels = (0..h * w)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                RGB::new(
+                    (x * depth as u32 / w) as u16,
+                    (y * depth as u32 / h) as u16,
+                    ((x * y) % (depth as u32 + 1)) as u16,
+                )
+            })
+            .collect();
+        PPMImg::from_pixels(h, w, depth, pixels)
+    }
+
+    #[test]
+    fn round_trips() {
+        let img = gradient(255);
+        assert_eq!(img, round_trip(&img));
+        let img = gradient(65535);
+        assert_eq!(img, round_trip(&img));
+
+        // other depths are scaled
+        let img = PPMImg::from_pixels(1, 2, 100, vec![RGB::new(100, 50, 0), RGB::gray(1)]);
+        assert_eq!(
+            PPMImg::from_pixels(1, 2, 255, vec![RGB::new(255, 128, 0), RGB::gray(3)]),
+            round_trip(&img)
+        );
+    }
+
+    /// A PNG with `header` fields and unfiltered `rows`
+    fn png(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: u8,
+        interlace: u8,
+        rows: &[&[u8]],
+        palette: &[u8],
+    ) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend(&width.to_be_bytes());
+        header.extend(&height.to_be_bytes());
+        header.extend(&[bit_depth, color_type, 0, 0, interlace]);
+        let mut data = vec![];
+        for row in rows {
+            data.push(0);
+            data.extend(*row);
+        }
+
+        let mut bytes = SIGNATURE.to_vec();
+        write_chunk(&mut bytes, b"IHDR", &header).unwrap();
+        if !palette.is_empty() {
+            write_chunk(&mut bytes, b"PLTE", palette).unwrap();
+        }
+        write_chunk(&mut bytes, b"tEXt", b"Comment\0ignored").unwrap();
+        write_chunk(&mut bytes, b"IDAT", &zlib::compress(&data)).unwrap();
+        write_chunk(&mut bytes, b"IEND", &[]).unwrap();
+        bytes
+    }
+
+    fn read(bytes: &[u8]) -> io::Result<Vec<RGB>> {
+        read_png(&mut &bytes[..]).map(|img| img.pixels().to_vec())
+    }
+
+    #[test]
+    fn color_types() {
+        let (black, white) = (RGB::gray(0), RGB::gray(255));
+
+        // 1-bit gray, 10 pixels per row
+        let bits = png(10, 1, 1, 0, 0, &[&[0b1010_0000, 0b0100_0000]], &[]);
+        let mut expected = vec![black; 10];
+        expected[0] = white;
+        expected[2] = white;
+        expected[9] = white;
+        assert_eq!(expected, read(&bits).unwrap());
+
+        // 4-bit palette
+        let palette = png(
+            3,
+            1,
+            4,
+            3,
+            0,
+            &[&[0x01, 0x20]],
+            &[1, 2, 3, 4, 5, 6, 7, 8, 9],
+        );
+        assert_eq!(
+            vec![RGB::new(1, 2, 3), RGB::new(4, 5, 6), RGB::new(7, 8, 9)],
+            read(&palette).unwrap()
+        );
+
+        // gray with alpha, and RGBA: alpha is dropped
+        let gray_alpha = png(1, 1, 8, 4, 0, &[&[200, 7]], &[]);
+        assert_eq!(vec![RGB::gray(200)], read(&gray_alpha).unwrap());
+        let rgba = png(1, 1, 8, 6, 0, &[&[1, 2, 3, 4]], &[]);
+        assert_eq!(vec![RGB::new(1, 2, 3)], read(&rgba).unwrap());
+    }
+
+    #[test]
+    fn interlaced() {
+        // 3 by 3 gray image, pixel value = index; passes 1, 4, 5, 6 and 7 have pixels
+        let rows: [&[u8]; 6] = [&[0], &[2], &[6, 8], &[1], &[7], &[3, 4, 5]];
+        let img = png(3, 3, 8, 0, 1, &rows, &[]);
+        let expected: Vec<RGB> = (0..9).map(RGB::gray).collect();
+        assert_eq!(expected, read(&img).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let mut bytes = vec![];
+        write_png(&gradient(255), &mut bytes).unwrap();
+        assert!(read(&bytes[..20]).is_err());
+        let n = bytes.len();
+        bytes[n - 20] ^= 0xff;
+        assert_eq!(ErrorKind::InvalidData, read(&bytes).unwrap_err().kind());
+        assert!(read(b"P6\n1 1 255\n\0\0\0").is_err());
+        assert!(read(&png(1, 1, 3, 2, 0, &[&[0]], &[])).is_err());
+
+        // sizes from the header are checked against the data before allocating pixels
+        let message = |bytes: &[u8]| read(bytes).unwrap_err().to_string();
+        assert_eq!(
+            "invalid PNG: image data should be 5000200000 bytes for a 200000x200000 image, found 2",
+            message(&png(200000, 200000, 1, 0, 0, &[&[0]], &[]))
+        );
+        assert_eq!(
+            "invalid PNG: image data should be 8 bytes for a 1x2 image, found 4",
+            message(&png(1, 2, 8, 2, 0, &[&[1, 2, 3]], &[]))
+        );
+        assert_eq!(
+            "invalid PNG: image is too large: 2147483648x1",
+            message(&png(1 << 31, 1, 8, 0, 0, &[&[0]], &[]))
+        );
+        assert_eq!(
+            "invalid PNG: image has no pixels",
+            message(&png(0, 1, 8, 0, 0, &[], &[]))
+        );
+
+        // chunk lengths aren't trusted either
+        let mut huge_chunk = SIGNATURE.to_vec();
+        huge_chunk.extend(&[0x7f, 0xff, 0xff, 0xff, b'I', b'H', b'D', b'R']);
+        assert_eq!(ErrorKind::UnexpectedEof, read(&huge_chunk).unwrap_err().kind());
+        // and data is only inflated up to the size of the image
+        assert_eq!(
+            "invalid zlib data: more than 2 bytes of data",
+            message(&png(1, 1, 8, 0, 0, &[&[0; 100_000]], &[]))
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! Keeping the solids drawn by a `Drawer` to save them as a 3d scene
+//!
+//! Solids are kept as they were drawn: moved by the coordinate stack, but not projected by a camera.
+//! Scenes can be saved as:
+//!
+//! - OBJ, with a `.mtl` file next to it holding a material for each material and color used
+//! - PLY, in ASCII, with a color for each face
+//! - STL, without colors
+
+use crate::graphics::{
+    material::Material, matrix::stl::StlFormat, render::RenderOptions, Matrix, RGB,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Material name used for solids drawn without one
+pub const DEFAULT_MATERIAL: &str = "default";
+
+/// A polygon matrix that was drawn, with what it was drawn with
+#[derive(Clone, Debug)]
+pub struct RecordedMesh {
+    pub polygons: Matrix,
+    /// Name of the material, `DEFAULT_MATERIAL` for the default one
+    pub material_name: String,
+    pub material: Material,
+    /// Color of each triangle if they weren't lit, see `FillMode`
+    pub colors: Option<Vec<RGB>>,
+}
+
+impl RecordedMesh {
+    /// The color triangle `i` was drawn with, or the color of its material if it was lit
+    pub fn color(&self, i: usize) -> RGB {
+        match &self.colors {
+            Some(colors) => colors[i],
+            None => self.material.base_color(),
+        }
+    }
+
+    /// Name of the `.mtl` material of triangle `i`
+    fn mtl_name(&self, i: usize) -> String {
+        match &self.colors {
+            Some(colors) => {
+                let c = colors[i];
+                format!("color_{:02x}{:02x}{:02x}", c.red, c.green, c.blue)
+            }
+            None => self.material_name.clone(),
+        }
+    }
+}
+
+/// The solids drawn since recording started
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    meshes: Vec<RecordedMesh>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Recording { meshes: Vec::new() }
+    }
+
+    /// Keep polygon matrix `m`, drawn with `material` named `material_name` and `options`
+    pub fn record(
+        &mut self,
+        m: &Matrix,
+        material_name: Option<&str>,
+        material: &Material,
+        options: &RenderOptions,
+    ) {
+        let colors = options.triangle_colors().map(|mut colors| {
+            m.surface_normals()
+                .into_iter()
+                .enumerate()
+                .map(|(i, normal)| colors.color(i, normal))
+                .collect()
+        });
+        self.meshes.push(RecordedMesh {
+            polygons: m.clone(),
+            material_name: material_name.unwrap_or(DEFAULT_MATERIAL).to_string(),
+            material: *material,
+            colors,
+        });
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.meshes.iter().map(|m| m.polygons.nrows() / 3).sum()
+    }
+
+    /// Every triangle in one polygon matrix
+    pub fn to_polygon_matrix(&self) -> Matrix {
+        let mut m = Matrix::new_polygon_matrix();
+        for mesh in self.meshes.iter() {
+            for row in mesh.polygons.iter_by_row() {
+                m.append_row(&mut row.to_vec());
+            }
+        }
+        m
+    }
+
+    /// Returns the vertices of all meshes without repeats, and the vertex index of each row
+    fn vertices(&self) -> (Vec<[f64; 3]>, Vec<usize>) {
+        let mut vertices = Vec::new();
+        let mut indices = HashMap::new();
+        let mut rows = Vec::new();
+        for mesh in self.meshes.iter() {
+            for row in mesh.polygons.iter_by_row() {
+                let v = [row[0], row[1], row[2]];
+                let key = [v[0].to_bits(), v[1].to_bits(), v[2].to_bits()];
+                let index = *indices.entry(key).or_insert_with(|| {
+                    vertices.push(v);
+                    vertices.len() - 1
+                });
+                rows.push(index);
+            }
+        }
+        (vertices, rows)
+    }
+
+    /// Write the scene as OBJ, using the materials in `mtllib` if it's given
+    pub fn write_obj(&self, mtllib: Option<&str>, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "# {} triangles", self.triangle_count())?;
+        if let Some(mtllib) = mtllib {
+            writeln!(out, "mtllib {}", mtllib)?;
+        }
+        let (vertices, rows) = self.vertices();
+        for v in vertices.iter() {
+            writeln!(out, "v {} {} {}", v[0], v[1], v[2])?;
+        }
+
+        let mut rows = rows.iter();
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            writeln!(out, "o mesh{}", i)?;
+            let mut current = None;
+            for t in 0..mesh.polygons.nrows() / 3 {
+                let name = mesh.mtl_name(t);
+                if current.as_ref() != Some(&name) {
+                    writeln!(out, "usemtl {}", name)?;
+                    current = Some(name);
+                }
+                // OBJ counts from 1
+                let mut index = || rows.next().unwrap() + 1;
+                writeln!(out, "f {} {} {}", index(), index(), index())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a material for every material and color used
+    pub fn write_mtl(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut written = Vec::new();
+        for mesh in self.meshes.iter() {
+            for t in 0..mesh.polygons.nrows() / 3 {
+                let name = mesh.mtl_name(t);
+                if written.contains(&name) {
+                    continue;
+                }
+                writeln!(out, "newmtl {}", name)?;
+                match mesh.colors {
+                    Some(_) => {
+                        let c = mesh.color(t);
+                        let (r, g, b) = (c.red as f64, c.green as f64, c.blue as f64);
+                        writeln!(out, "Kd {} {} {}", r / 255., g / 255., b / 255.)?;
+                    }
+                    None => {
+                        let m = &mesh.material;
+                        let (a, d, s) = (m.ambient, m.diffuse, m.specular);
+                        writeln!(out, "Ka {} {} {}", a.0, a.1, a.2)?;
+                        writeln!(out, "Kd {} {} {}", d.0, d.1, d.2)?;
+                        writeln!(out, "Ks {} {} {}", s.0, s.1, s.2)?;
+                        writeln!(out, "Ns {}", m.shininess)?;
+                    }
+                }
+                writeln!(out)?;
+                written.push(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the scene as ASCII PLY, with the color of each face
+    pub fn write_ply(&self, out: &mut dyn Write) -> io::Result<()> {
+        let (vertices, rows) = self.vertices();
+        writeln!(out, "ply")?;
+        writeln!(out, "format ascii 1.0")?;
+        writeln!(out, "element vertex {}", vertices.len())?;
+        for axis in ["x", "y", "z"].iter() {
+            writeln!(out, "property double {}", axis)?;
+        }
+        writeln!(out, "element face {}", self.triangle_count())?;
+        writeln!(out, "property list uchar int vertex_indices")?;
+        for channel in ["red", "green", "blue"].iter() {
+            writeln!(out, "property uchar {}", channel)?;
+        }
+        writeln!(out, "end_header")?;
+
+        for v in vertices.iter() {
+            writeln!(out, "{} {} {}", v[0], v[1], v[2])?;
+        }
+        let mut rows = rows.iter();
+        for mesh in self.meshes.iter() {
+            for t in 0..mesh.polygons.nrows() / 3 {
+                let mut index = || rows.next().unwrap();
+                let (a, b, c) = (index(), index(), index());
+                let color = mesh.color(t);
+                let channel = |v: u16| v.min(255);
+                writeln!(
+                    out,
+                    "3 {} {} {} {} {} {}",
+                    a,
+                    b,
+                    c,
+                    channel(color.red),
+                    channel(color.green),
+                    channel(color.blue)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the scene to `filepath` as OBJ, PLY or STL, from its extension
+    ///
+    /// OBJ files get their materials in a `.mtl` file of the same name.
+    pub fn save(&self, filepath: &str) -> io::Result<()> {
+        let path = Path::new(filepath);
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let create = |path: &Path| File::create(path).map(BufWriter::new);
+        match extension.as_str() {
+            "obj" => {
+                let mtl_path = path.with_extension("mtl");
+                let mtllib = mtl_path.file_name().unwrap().to_string_lossy();
+                let mut out = create(path)?;
+                self.write_obj(Some(&mtllib), &mut out)?;
+                out.flush()?;
+                let mut mtl = create(&mtl_path)?;
+                self.write_mtl(&mut mtl)?;
+                mtl.flush()
+            }
+            "ply" => {
+                let mut out = create(path)?;
+                self.write_ply(&mut out)?;
+                out.flush()
+            }
+            "stl" => self
+                .to_polygon_matrix()
+                .save_stl(filepath, StlFormat::Binary),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "scenes can only be saved as .obj, .ply or .stl",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::render::FillMode;
+
+    fn scene() -> Recording {
+        let mut m = Matrix::new_polygon_matrix();
+        m.append_polygon((0., 0., 0.), (1., 0., 0.), (0., 1., 0.));
+        m.append_polygon((1., 0., 0.), (1., 1., 0.), (0., 1., 0.));
+
+        let mut recording = Recording::new();
+        recording.record(&m, None, &Material::default(), &RenderOptions::default());
+        let red = RGB::new(255, 0, 0);
+        let options = RenderOptions {
+            fill: FillMode::Fixed(red),
+            ..Default::default()
+        };
+        let mut moved = Matrix::new_polygon_matrix();
+        moved.append_polygon((0., 0., 1.5), (1., 0., 1.5), (0., 1., 1.5));
+        recording.record(
+            &moved,
+            Some("shiny"),
+            &Material::default().shiny(),
+            &options,
+        );
+        recording
+    }
+
+    fn written(write: impl Fn(&Recording, &mut dyn Write) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&scene(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn obj() {
+        assert_eq!(
+            "# 3 triangles
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+v 0 0 1.5
+v 1 0 1.5
+v 0 1 1.5
+o mesh0
+usemtl default
+f 1 2 3
+f 2 4 3
+o mesh1
+usemtl color_ff0000
+f 5 6 7
+",
+            written(|r, out| r.write_obj(Some("scene.mtl"), out))
+        );
+        assert_eq!(
+            "newmtl default
+Ka 0.1 0.1 0.1
+Kd 0.5 0.5 0.5
+Ks 0.5 0.5 0.5
+Ns 8
+
+newmtl color_ff0000
+Kd 1 0 0
+
+",
+            written(|r, out| r.write_mtl(out))
+        );
+    }
+
+    #[test]
+    fn ply() {
+        let ply = written(|r, out| r.write_ply(out));
+        let (header, body) = ply.split_at(ply.find("end_header\n").unwrap());
+        assert!(header.contains("element vertex 7\n"));
+        assert!(header.contains("element face 3\n"));
+        assert_eq!(
+            "end_header
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1.5
+1 0 1.5
+0 1 1.5
+3 0 1 2 153 153 153
+3 1 3 2 153 153 153
+3 4 5 6 255 0 0
+",
+            body
+        );
+    }
+
+    #[test]
+    fn saves_by_extension() {
+        let dir = std::env::temp_dir().join("w8_solids_recording");
+        std::fs::create_dir_all(&dir).unwrap();
+        let recording = scene();
+        for name in ["scene.obj", "scene.ply", "scene.stl"].iter() {
+            recording.save(&dir.join(name).to_string_lossy()).unwrap();
+        }
+        assert!(dir.join("scene.mtl").exists());
+        let mut m = Matrix::new_polygon_matrix();
+        m.add_mesh_obj(&dir.join("scene.obj").to_string_lossy())
+            .unwrap();
+        let rows = |m: &Matrix| -> Vec<Vec<f64>> { m.iter_by_row().map(|r| r.to_vec()).collect() };
+        assert_eq!(rows(&recording.to_polygon_matrix()), rows(&m));
+        assert!(recording
+            .save(&dir.join("scene.png").to_string_lossy())
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
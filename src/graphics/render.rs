@@ -0,0 +1,135 @@
+//! Options controlling how polygons are filled
+
+use crate::graphics::{light::ShadingMode, vector::Vec3, RGB};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Where the color of each triangle comes from
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum FillMode {
+    /// Lit by the light sources, shaded according to the shading mode
+    #[default]
+    Lit,
+    /// A random color for each triangle
+    ///
+    /// The generator is seeded with `seed` at the start of every polygon matrix,
+    /// so the same solid always comes out in the same colors.
+    Random { seed: u64 },
+    /// The same color for every triangle
+    Fixed(RGB),
+    /// Triangle `i` of a polygon matrix gets color `i % len`, see `FillMode::palette`
+    ///
+    /// Triangles are lit if there are no colors.
+    Palette(Vec<RGB>),
+    /// Each triangle colored by its surface normal, with x, y and z mapped from [-1, 1] to red, green and blue
+    Normals,
+}
+
+impl FillMode {
+    /// Returns a `Palette` of `colors`, or `None` if there are no colors
+    pub fn palette(colors: Vec<RGB>) -> Option<FillMode> {
+        if colors.is_empty() {
+            None
+        } else {
+            Some(FillMode::Palette(colors))
+        }
+    }
+}
+
+/// Settings for rendering polygon matrices
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct RenderOptions {
+    pub fill: FillMode,
+    /// Only used when `fill` is `FillMode::Lit`
+    pub shading: ShadingMode,
+}
+
+impl RenderOptions {
+    /// Returns a `TriangleColors` that picks the color of each triangle of a polygon matrix,
+    /// or `None` if triangles should be lit
+    pub fn triangle_colors(&self) -> Option<TriangleColors<'_>> {
+        match &self.fill {
+            FillMode::Lit => None,
+            FillMode::Palette(colors) if colors.is_empty() => None,
+            fill => Some(TriangleColors {
+                fill,
+                rng: match fill {
+                    FillMode::Random { seed } => Some(StdRng::seed_from_u64(*seed)),
+                    _ => None,
+                },
+            }),
+        }
+    }
+}
+
+/// Colors for the triangles of one polygon matrix, in order
+pub struct TriangleColors<'a> {
+    fill: &'a FillMode,
+    rng: Option<StdRng>,
+}
+
+impl TriangleColors<'_> {
+    /// Returns the color of triangle `index` with surface normal `normal`
+    ///
+    /// Must be called for every triangle, including culled ones,
+    /// so that random colors don't depend on what's visible.
+    pub fn color(&mut self, index: usize, normal: Vec3) -> RGB {
+        match self.fill {
+            FillMode::Lit => panic!("Lit triangles don't have a fixed color"),
+            FillMode::Random { .. } => {
+                let rng = self.rng.as_mut().unwrap();
+                RGB::new(
+                    rng.gen_range(0, 256),
+                    rng.gen_range(0, 256),
+                    rng.gen_range(0, 256),
+                )
+            }
+            FillMode::Fixed(color) => *color,
+            FillMode::Palette(colors) => colors[index % colors.len()],
+            FillMode::Normals => ((normal.norm() + Vec3(1., 1., 1.)) * 127.5).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(fill: FillMode, n: usize) -> Vec<RGB> {
+        let options = RenderOptions {
+            fill,
+            ..Default::default()
+        };
+        let mut colors = options.triangle_colors().unwrap();
+        (0..n).map(|i| colors.color(i, Vec3(0., 0., 1.))).collect()
+    }
+
+    #[test]
+    fn fill_modes() {
+        assert!(RenderOptions::default().triangle_colors().is_none());
+
+        let red = RGB::new(255, 0, 0);
+        assert_eq!(vec![red; 3], colors(FillMode::Fixed(red), 3));
+
+        let palette = vec![red, RGB::gray(0)];
+        assert_eq!(
+            vec![red, RGB::gray(0), red],
+            colors(FillMode::Palette(palette), 3)
+        );
+
+        assert_eq!(vec![RGB::new(128, 128, 255)], colors(FillMode::Normals, 1));
+
+        assert_eq!(None, FillMode::palette(vec![]));
+        let empty = RenderOptions {
+            fill: FillMode::Palette(vec![]),
+            ..Default::default()
+        };
+        assert!(empty.triangle_colors().is_none());
+    }
+
+    #[test]
+    fn random_is_seeded() {
+        let a = colors(FillMode::Random { seed: 1 }, 10);
+        assert_eq!(a, colors(FillMode::Random { seed: 1 }, 10));
+        assert_ne!(a, colors(FillMode::Random { seed: 2 }, 10));
+    }
+}
@@ -0,0 +1,345 @@
+//! A canvas that draws SVG shapes instead of pixels
+//!
+//! Lines become `<line>` elements and triangles `<polygon>` elements, so drawings stay sharp at any size.
+//! There is no z-buffer: shapes are sorted by depth and drawn from back to front
+//! (the painter's algorithm), which is right as long as triangles don't cut through each other.
+//! Shading is done per triangle, so Gouraud and Phong shading come out flat.
+
+use crate::graphics::{
+    light::{LightConfig, ShadingMode},
+    material::Material,
+    render::RenderOptions,
+    vector::Vec3,
+    Canvas, Matrix, PPMImg, RGB,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Shape {
+    /// A single pixel, from `plot`
+    Point {
+        x: i32,
+        y: i32,
+    },
+    Line {
+        p0: Vec3,
+        p1: Vec3,
+    },
+    Triangle {
+        v: [Vec3; 3],
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Element {
+    shape: Shape,
+    color: RGB,
+    /// Larger is closer, like the z-buffer
+    depth: f64,
+}
+
+pub struct SvgCanvas {
+    width: u32,
+    height: u32,
+    fg_color: RGB,
+    bg_color: RGB,
+    elements: Vec<Element>,
+}
+
+impl SvgCanvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        SvgCanvas {
+            width,
+            height,
+            fg_color: RGB::gray(255),
+            bg_color: RGB::gray(0),
+            elements: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, shape: Shape, depth: f64) {
+        self.elements.push(Element {
+            shape,
+            color: self.fg_color,
+            depth,
+        });
+    }
+
+    /// Elements from back to front, in the order they were drawn when at the same depth
+    fn sorted(&self) -> Vec<&Element> {
+        let mut elements: Vec<&Element> = self.elements.iter().collect();
+        elements.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+        elements
+    }
+
+    /// `(x, y)` of `p` in SVG coordinates, where y goes down from the top
+    ///
+    /// Pixels are squares with integer coordinates at their centers, like in a `PPMImg`.
+    fn to_svg(&self, p: Vec3) -> (f64, f64) {
+        (p.0 + 0.5, self.height as f64 - p.1 - 0.5)
+    }
+}
+
+/// `color` as `#rrggbb`, with channels above 255 clamped
+fn hex(color: RGB) -> String {
+    let c = |v: u16| v.min(255);
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        c(color.red),
+        c(color.green),
+        c(color.blue)
+    )
+}
+
+/// Writes `x` with at most 2 decimals, which is plenty for pixels
+fn num(x: f64) -> String {
+    let s = format!("{:.2}", x);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        String::from("0")
+    } else {
+        s.to_string()
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn plot(&mut self, x: i32, y: i32, z: f64) {
+        self.push(Shape::Point { x, y }, z);
+    }
+
+    fn set_fg_color(&mut self, color: RGB) {
+        self.fg_color = color;
+    }
+    fn set_bg_color(&mut self, color: RGB) {
+        self.bg_color = color;
+    }
+    fn get_fg_color(&self) -> RGB {
+        self.fg_color
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Save as SVG if `filepath` ends in `.svg`, otherwise as the image `to_ppm` returns
+    fn save(&self, filepath: &str) -> io::Result<()> {
+        if filepath.to_ascii_lowercase().ends_with(".svg") {
+            let mut out = BufWriter::new(File::create(filepath)?);
+            self.write_to_buf(&mut out)?;
+            out.flush()
+        } else {
+            self.to_ppm().save(filepath)
+        }
+    }
+
+    /// Write the drawing as an SVG document
+    fn write_to_buf(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height
+        )?;
+        writeln!(
+            out,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            hex(self.bg_color)
+        )?;
+        for element in self.sorted() {
+            let color = hex(element.color);
+            match &element.shape {
+                Shape::Point { x, y } => writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="1" height="1" fill="{}"/>"#,
+                    x,
+                    self.height as i32 - 1 - y,
+                    color
+                )?,
+                Shape::Line { p0, p1 } => {
+                    let ((x0, y0), (x1, y1)) = (self.to_svg(*p0), self.to_svg(*p1));
+                    writeln!(
+                        out,
+                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}"/>"#,
+                        num(x0),
+                        num(y0),
+                        num(x1),
+                        num(y1),
+                        color
+                    )?
+                }
+                Shape::Triangle { v } => {
+                    let points: Vec<String> = v
+                        .iter()
+                        .map(|&p| {
+                            let (x, y) = self.to_svg(p);
+                            format!("{},{}", num(x), num(y))
+                        })
+                        .collect();
+                    // the stroke covers the seams between triangles
+                    writeln!(
+                        out,
+                        r#"<polygon points="{}" fill="{c}" stroke="{c}" stroke-width="0.5"/>"#,
+                        points.join(" "),
+                        c = color
+                    )?
+                }
+            }
+        }
+        writeln!(out, "</svg>")
+    }
+
+    fn display(&self) {
+        self.to_ppm().display();
+    }
+
+    fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    /// Rasterize the drawing, with the z-buffer of a `PPMImg`
+    fn to_ppm(&self) -> PPMImg {
+        let mut img = PPMImg::new(self.height, self.width, 255);
+        img.set_bg_color(self.bg_color);
+        img.clear();
+        for element in self.elements.iter() {
+            img.set_fg_color(element.color);
+            match element.shape {
+                Shape::Point { x, y } => img.plot(x, y, element.depth),
+                Shape::Line { p0, p1 } => img.draw_line((p0.0, p0.1, p0.2), (p1.0, p1.1, p1.2)),
+                Shape::Triangle { v } => {
+                    let color = element.color;
+                    img.fill_triangle(v, [Vec3(0., 0., 0.); 3], &|_, _| color)
+                }
+            }
+        }
+        img
+    }
+
+    fn draw_line(&mut self, p0: (f64, f64, f64), p1: (f64, f64, f64)) {
+        let (p0, p1) = (Vec3::from_pt(p0), Vec3::from_pt(p1));
+        self.push(Shape::Line { p0, p1 }, p0.2.max(p1.2));
+    }
+
+    /// Adds a polygon for each triangle facing the viewer, colored like `PPMImg` would
+    /// with flat shading
+    fn render_projected_polygon_matrix(
+        &mut self,
+        m: &Matrix,
+        space: &Matrix,
+        light: &LightConfig,
+        material: &Material,
+        options: &RenderOptions,
+    ) {
+        let orig_color = self.get_fg_color();
+        let max = self.depth();
+        let mut triangle_colors = options.triangle_colors();
+        let vertex_normals = match options.shading {
+            ShadingMode::Gouraud | ShadingMode::Phong if triangle_colors.is_none() => {
+                space.vertex_normals()
+            }
+            _ => vec![],
+        };
+
+        let vertices =
+            |m: &Matrix| -> Vec<Vec3> { m.iter_by_row().map(|p| Vec3(p[0], p[1], p[2])).collect() };
+        let (rows, space_rows) = (vertices(m), vertices(space));
+        let space_normals = space.surface_normals();
+        for (i, (triangle, normal)) in rows.chunks(3).zip(m.surface_normals()).enumerate() {
+            let v = [triangle[0], triangle[1], triangle[2]];
+            let fixed_color = triangle_colors.as_mut().map(|c| c.color(i, normal));
+            if normal.2 <= 0. {
+                continue;
+            }
+
+            let s = &space_rows[i * 3..i * 3 + 3];
+            let center = (s[0] + s[1] + s[2]) / 3.;
+            let color = match fixed_color {
+                Some(color) => color,
+                None if vertex_normals.is_empty() => {
+                    light.get_color(space_normals[i], center, material, max)
+                }
+                // one color for the whole triangle, lit by its average vertex normal
+                None => {
+                    let n = &vertex_normals[i * 3..i * 3 + 3];
+                    light.get_color(n[0] + n[1] + n[2], center, material, max)
+                }
+            };
+            self.set_fg_color(color);
+            self.push(Shape::Triangle { v }, (v[0].2 + v[1].2 + v[2].2) / 3.);
+        }
+        self.set_fg_color(orig_color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{
+        golden::{compare, Tolerance},
+        render::FillMode,
+    };
+
+    #[test]
+    fn lines() {
+        let mut svg = SvgCanvas::new(10, 10);
+        svg.set_fg_color(RGB::new(255, 0, 0));
+        let mut m = Matrix::new_edge_matrix();
+        m.append_edge(&[0., 0., 0., 9., 9., 0.]);
+        svg.render_edge_matrix(&m);
+
+        let mut out = Vec::new();
+        svg.write_to_buf(&mut out).unwrap();
+        assert_eq!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10" viewBox="0 0 10 10">
+<rect width="100%" height="100%" fill="#000000"/>
+<line x1="0.5" y1="9.5" x2="9.5" y2="0.5" stroke="#ff0000"/>
+</svg>
+"##,
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn painters_algorithm() {
+        let (near, far) = (RGB::new(255, 0, 0), RGB::new(0, 0, 255));
+        let options = |color| RenderOptions {
+            fill: FillMode::Fixed(color),
+            ..Default::default()
+        };
+        let square = |z: f64| {
+            let mut m = Matrix::new_polygon_matrix();
+            m.append_polygon((0., 0., z), (20., 0., z), (0., 20., z));
+            m
+        };
+
+        let mut svg = SvgCanvas::new(20, 20);
+        let (light, material) = (LightConfig::default(), Material::default());
+        // near first, so drawing in order would put far on top
+        svg.render_polygon_matrix(&square(10.), &light, &material, &options(near));
+        svg.render_polygon_matrix(&square(-10.), &light, &material, &options(far));
+        // facing away
+        let mut back = Matrix::new_polygon_matrix();
+        back.append_polygon((0., 0., 20.), (0., 20., 20.), (20., 0., 20.));
+        svg.render_polygon_matrix(&back, &light, &material, &options(far));
+
+        let mut out = Vec::new();
+        svg.write_to_buf(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let polygons: Vec<&str> = out.lines().filter(|l| l.starts_with("<polygon")).collect();
+        assert_eq!(2, polygons.len());
+        assert!(polygons[0].contains("#0000ff") && polygons[1].contains("#ff0000"));
+        assert!(polygons[1].starts_with(r#"<polygon points="0.5,19.5 20.5,19.5 0.5,-0.5""#));
+
+        // rasterizes the same as drawing on an image directly
+        let mut img = PPMImg::new(20, 20, 255);
+        img.render_polygon_matrix(&square(10.), &light, &material, &options(near));
+        img.render_polygon_matrix(&square(-10.), &light, &material, &options(far));
+        let c = compare(&img, &svg.to_ppm(), Tolerance::exact());
+        assert_eq!(0, c.unwrap().diff_pixels);
+    }
+}
@@ -0,0 +1,264 @@
+//! Showing images right in the terminal, for when there is no window to display them in
+//!
+//! Two kinds of output are supported:
+//!
+//! - half blocks: each character is an upper half block `▀` colored with ANSI 24-bit colors,
+//!   the foreground being the top pixel and the background the bottom pixel.
+//!   The image is scaled down to fit the width of the terminal. Works in most terminals.
+//! - sixel: the image at full size, with its colors reduced to a 6x6x6 color cube.
+//!   Needs a terminal with sixel graphics, like xterm -ti vt340, mlterm, foot or WezTerm.
+
+use crate::graphics::{utils, Canvas, PPMImg, RGB};
+use std::{
+    env,
+    io::{self, Write},
+};
+
+/// Terminal width used when `COLUMNS` isn't set
+pub const DEFAULT_COLUMNS: usize = 80;
+
+/// How `display` shows images
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DisplayMode {
+    /// In a window, with ImageMagick's `display`
+    #[default]
+    Window,
+    /// In the terminal, with ANSI colored half blocks
+    HalfBlocks,
+    /// In the terminal, with sixel graphics
+    Sixel,
+}
+
+impl DisplayMode {
+    /// Returns the mode named `name`: `window`, `ansi` or `sixel`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "window" | "magick" => Some(DisplayMode::Window),
+            "ansi" | "halfblocks" => Some(DisplayMode::HalfBlocks),
+            "sixel" => Some(DisplayMode::Sixel),
+            _ => None,
+        }
+    }
+}
+
+/// Show `img` as `mode`
+pub fn display(img: &PPMImg, mode: DisplayMode) {
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let result = match mode {
+        DisplayMode::Window => {
+            utils::display_ppm(img);
+            Ok(())
+        }
+        DisplayMode::HalfBlocks => write_half_blocks(img, columns(), &mut out),
+        DisplayMode::Sixel => write_sixel(img, &mut out),
+    };
+    result.expect("Error writing image to the terminal");
+}
+
+/// Width of the terminal in characters, from `COLUMNS`
+pub fn columns() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(DEFAULT_COLUMNS)
+}
+
+/// Color of `c` in an image of `depth`, with channels scaled to 0-255
+fn to_8bit(c: RGB, depth: u16) -> [u8; 3] {
+    let scale = |v: u16| ((v as u32 * 255 + depth as u32 / 2) / depth as u32) as u8;
+    [scale(c.red), scale(c.green), scale(c.blue)]
+}
+
+/// Returns `img` scaled down to `width` by `height` 8-bit pixels, each the average of the pixels it covers
+fn downsample(img: &PPMImg, width: usize, height: usize) -> Vec<[u8; 3]> {
+    let (src_width, src_height) = (img.width() as usize, img.height() as usize);
+    // pixels of the source covered by pixel `i` of `n` along a side of `len` pixels
+    let span = |i: usize, n: usize, len: usize| {
+        let start = i * len / n;
+        start..((i + 1) * len / n).max(start + 1)
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            for sy in span(y, height, src_height) {
+                for sx in span(x, width, src_width) {
+                    let c = to_8bit(img.pixels()[sy * src_width + sx], img.depth());
+                    for (s, c) in sum.iter_mut().zip(c.iter()) {
+                        *s += *c as u32;
+                    }
+                    count += 1;
+                }
+            }
+            pixels.push([
+                ((sum[0] + count / 2) / count) as u8,
+                ((sum[1] + count / 2) / count) as u8,
+                ((sum[2] + count / 2) / count) as u8,
+            ]);
+        }
+    }
+    pixels
+}
+
+/// Write `img` as rows of half blocks at most `columns` wide
+///
+/// Images are never scaled up. Since two pixels go in each character,
+/// the image keeps its proportions in terminals with characters twice as tall as they're wide.
+pub fn write_half_blocks(img: &PPMImg, columns: usize, out: &mut dyn Write) -> io::Result<()> {
+    let width = columns.min(img.width() as usize).max(1);
+    let height = (img.height() as usize * width) / img.width() as usize;
+    // round up to whole characters
+    let height = (height + height % 2).max(2);
+    let pixels = downsample(img, width, height);
+
+    for row in pixels.chunks(width * 2) {
+        let (top, bottom) = row.split_at(width);
+        let mut colors = None;
+        for (t, b) in top.iter().zip(bottom) {
+            if colors != Some((t, b)) {
+                write!(
+                    out,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                    t[0], t[1], t[2], b[0], b[1], b[2]
+                )?;
+                colors = Some((t, b));
+            }
+            write!(out, "\u{2580}")?;
+        }
+        writeln!(out, "\x1b[0m")?;
+    }
+    out.flush()
+}
+
+/// Levels of each channel in the sixel palette
+const SIXEL_LEVELS: usize = 6;
+
+/// Index of the color closest to `c` in the sixel palette
+fn sixel_color(c: [u8; 3]) -> usize {
+    let level = |v: u8| (v as usize * (SIXEL_LEVELS - 1) + 127) / 255;
+    (level(c[0]) * SIXEL_LEVELS + level(c[1])) * SIXEL_LEVELS + level(c[2])
+}
+
+/// Write a run of `count` sixels `c`, using a repeat when it's shorter
+fn write_sixel_run(out: &mut dyn Write, c: u8, count: usize) -> io::Result<()> {
+    match count {
+        0 => Ok(()),
+        1..=3 => out.write_all(&vec![c; count]),
+        _ => write!(out, "!{}{}", count, c as char),
+    }
+}
+
+/// Write `img` as sixel graphics
+pub fn write_sixel(img: &PPMImg, out: &mut dyn Write) -> io::Result<()> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let colors: Vec<usize> = img
+        .pixels()
+        .iter()
+        .map(|&c| sixel_color(to_8bit(c, img.depth())))
+        .collect();
+
+    // start sixels with square pixels, and the size of the image
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height)?;
+    let mut used = vec![false; SIXEL_LEVELS * SIXEL_LEVELS * SIXEL_LEVELS];
+    for &c in colors.iter() {
+        used[c] = true;
+    }
+    let percent = |level: usize| level * 100 / (SIXEL_LEVELS - 1);
+    let levels = SIXEL_LEVELS;
+    for (i, _) in used.iter().enumerate().filter(|(_, &used)| used) {
+        write!(
+            out,
+            "#{};2;{};{};{}",
+            i,
+            percent(i / levels / levels),
+            percent(i / levels % levels),
+            percent(i % levels)
+        )?;
+    }
+
+    // each band is 6 pixels tall, drawn once per color in it
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut band_colors: Vec<usize> = rows
+            .clone()
+            .flat_map(|y| colors[y * width..(y + 1) * width].iter().copied())
+            .collect();
+        band_colors.sort_unstable();
+        band_colors.dedup();
+
+        for (i, &color) in band_colors.iter().enumerate() {
+            if i > 0 {
+                // back to the start of the band
+                write!(out, "$")?;
+            }
+            write!(out, "#{}", color)?;
+            let (mut run, mut count) = (0, 0);
+            for x in 0..width {
+                let bits = rows
+                    .clone()
+                    .enumerate()
+                    .filter(|&(_, y)| colors[y * width + x] == color)
+                    .fold(0, |bits, (k, _)| bits | 1 << k);
+                let c = 63 + bits as u8;
+                if c != run && count > 0 {
+                    write_sixel_run(out, run, count)?;
+                    count = 0;
+                }
+                run = c;
+                count += 1;
+            }
+            write_sixel_run(out, run, count)?;
+        }
+        write!(out, "-")?;
+    }
+    write!(out, "\x1b\\")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_blocks() {
+        let (red, blue) = (RGB::new(255, 0, 0), RGB::new(0, 0, 255));
+        let img = PPMImg::from_pixels(2, 2, 255, vec![red, red, blue, blue]);
+        let mut out = Vec::new();
+        write_half_blocks(&img, 80, &mut out).unwrap();
+        assert_eq!(
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}\u{2580}\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+
+        // scaled down to fit, averaging the pixels
+        let img = PPMImg::from_pixels(4, 4, 65535, vec![RGB::gray(65535); 16]);
+        let mut out = Vec::new();
+        write_half_blocks(&img, 2, &mut out).unwrap();
+        assert_eq!(
+            "\x1b[38;2;255;255;255m\x1b[48;2;255;255;255m\u{2580}\u{2580}\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn sixel() {
+        let (red, black) = (RGB::new(255, 0, 0), RGB::gray(0));
+        // 5 pixels wide, 7 tall: red on the left, black on the right, two bands
+        let pixels = (0..35)
+            .map(|i| if i % 5 < 4 { red } else { black })
+            .collect();
+        let img = PPMImg::from_pixels(7, 5, 255, pixels);
+        let mut out = Vec::new();
+        write_sixel(&img, &mut out).unwrap();
+        assert_eq!(
+            "\x1bP0;1;0q\"1;1;5;7#0;2;0;0;0#180;2;100;0;0\
+             #0!4?~$#180!4~?-\
+             #0!4?@$#180!4@?-\x1b\\",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
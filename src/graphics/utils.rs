@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::fs::File;
 
 use std::path::Path;
@@ -7,7 +9,7 @@ use crate::graphics::canvas::Canvas;
 pub(crate) fn create_file(filepath: &str) -> File {
     let path = Path::new(filepath);
     let display = path.display();
-    match File::create(&path) {
+    match File::create(path) {
         Err(why) => panic!("Could not create {}: {}", display, why),
         Ok(file) => file,
     }
@@ -46,7 +48,12 @@ pub(crate) fn compute_hermite3_coef(p0: f64, p1: f64, r0: f64, r1: f64) -> (f64,
     )
 }
 
-use crate::graphics::{Matrix, PPMImg};
+use crate::graphics::{
+    light::LightConfig,
+    material::Material,
+    render::RenderOptions,
+    Matrix, PPMImg,
+};
 use std::{fs, process::Command};
 
 pub(crate) fn display_ppm(img: &PPMImg) {
@@ -85,7 +92,12 @@ pub(crate) fn display_polygon_matrix(m: &Matrix, ndc: bool) {
     if ndc {
         unimplemented!("Displaying polygon matrix in ndc is not implemented.");
     } else {
-        img.render_polygon_matrix(m);
+        img.render_polygon_matrix(
+            m,
+            &LightConfig::default(),
+            &Material::default(),
+            &RenderOptions::default(),
+        );
     }
     display_ppm(&img);
 }
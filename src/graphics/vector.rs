@@ -5,7 +5,7 @@ pub struct Vec3(pub f64, pub f64, pub f64);
 
 impl Vec3 {
     pub fn _dot(a: &Self, b: &Self) -> f64 {
-        a.0 * b.0 + a.1 * b.1 + a.2 + b.2
+        a.0 * b.0 + a.1 * b.1 + a.2 * b.2
     }
 
     pub fn _cross(a: &Self, b: &Self) -> Self {
@@ -29,7 +29,7 @@ impl Vec3 {
 
 impl Vec3 {
     pub fn dot(&self, other: Self) -> f64 {
-        self.0 * other.0 + self.1 * other.1 + self.2 + other.2
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2
     }
 
     pub fn cross(&self, other: Self) -> Self {
@@ -43,6 +43,22 @@ impl Vec3 {
     pub fn from_pt(point: (f64, f64, f64)) -> Self {
         Vec3(point.0, point.1, point.2)
     }
+
+    pub fn mag(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    /// Returns a vector with the same direction and a magnitude of 1
+    ///
+    /// The zero vector is returned unchanged
+    pub fn norm(&self) -> Self {
+        let mag = self.mag();
+        if mag == 0. {
+            *self
+        } else {
+            *self / mag
+        }
+    }
 }
 
 impl ops::Mul for Vec3 {
@@ -72,3 +88,17 @@ impl ops::Div<f64> for Vec3 {
         Vec3(self.0 / rhs, self.1 / rhs, self.2 / rhs)
     }
 }
+
+impl ops::Mul<f64> for Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: f64) -> Self::Output {
+        Vec3(self.0 * rhs, self.1 * rhs, self.2 * rhs)
+    }
+}
+
+impl ops::Neg for Vec3 {
+    type Output = Vec3;
+    fn neg(self) -> Self::Output {
+        Vec3(-self.0, -self.1, -self.2)
+    }
+}
@@ -0,0 +1,698 @@
+//! zlib streams (RFC 1950) with deflate compression (RFC 1951), used by PNG
+//!
+//! Compression finds repeats with hash chains and codes each block with its own Huffman codes.
+//! Decompression handles stored, fixed and dynamic Huffman blocks.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io::{self, ErrorKind},
+};
+
+const WINDOW: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions to try when looking for a match
+const MAX_CHAIN: usize = 128;
+/// Symbols per block, each block gets its own codes
+const BLOCK_SYMBOLS: usize = 1 << 16;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order the code length code lengths are stored in
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid zlib data: {}", msg),
+    )
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+// compression
+
+/// A literal byte or a repeat of earlier data
+#[derive(Copy, Clone)]
+enum Symbol {
+    Literal(u8),
+    Match { length: u16, dist: u16 },
+}
+
+/// Index of the code for `value` in a base table
+fn code_index(bases: &[u16], value: u16) -> usize {
+    bases.iter().rposition(|&b| b <= value).unwrap()
+}
+
+/// Find repeats in `data`
+fn lz77(data: &[u8]) -> Vec<Symbol> {
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff
+    };
+    // most recent position of each hash, and the position before it with the same hash
+    let mut head = vec![usize::MAX; 1 << 15];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut symbols = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, i - candidate);
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best.0 >= MIN_MATCH {
+            symbols.push(Symbol::Match {
+                length: best.0 as u16,
+                dist: best.1 as u16,
+            });
+            for j in i..i + best.0 {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best.0;
+        } else {
+            symbols.push(Symbol::Literal(data[i]));
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    symbols
+}
+
+/// Huffman code lengths for symbols used `freqs` times, at most `max_len` bits long
+fn code_lengths(freqs: &[u32], max_len: usize) -> Vec<u8> {
+    let mut lengths = vec![0; freqs.len()];
+    let used: Vec<usize> = (0..freqs.len()).filter(|&s| freqs[s] > 0).collect();
+    match used.len() {
+        0 => return lengths,
+        1 => {
+            lengths[used[0]] = 1;
+            return lengths;
+        }
+        _ => {}
+    }
+
+    // build the tree, nodes past the symbols are internal
+    let mut parent = vec![0; freqs.len()];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = used
+        .iter()
+        .map(|&s| Reverse((freqs[s] as u64, s)))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((f0, n0)) = heap.pop().unwrap();
+        let Reverse((f1, n1)) = heap.pop().unwrap();
+        let node = parent.len();
+        parent.push(0);
+        parent[n0] = node;
+        parent[n1] = node;
+        heap.push(Reverse((f0 + f1, node)));
+    }
+    let root = parent.len() - 1;
+    let depth = |mut n: usize| {
+        let mut d = 0;
+        while n != root {
+            n = parent[n];
+            d += 1;
+        }
+        d
+    };
+
+    // count codes of each length, then move codes that are too long up to max_len
+    let mut counts = vec![0usize; max_len + 1];
+    for &s in used.iter() {
+        counts[depth(s).min(max_len)] += 1;
+    }
+    let kraft = |counts: &[usize]| -> usize {
+        (1..=max_len)
+            .map(|len| counts[len] << (max_len - len))
+            .sum()
+    };
+    while kraft(&counts) > 1 << max_len {
+        // make a code one longer to make room: split the longest one shorter than max
+        counts[max_len] -= 1;
+        let len = (1..max_len).rev().find(|&len| counts[len] > 0).unwrap();
+        counts[len] -= 1;
+        counts[len + 1] += 2;
+    }
+
+    // give the shortest codes to the most frequent symbols
+    let mut by_freq = used;
+    by_freq.sort_by_key(|&s| Reverse(freqs[s]));
+    let mut by_freq = by_freq.into_iter();
+    for (len, &count) in counts.iter().enumerate().skip(1) {
+        for s in by_freq.by_ref().take(count) {
+            lengths[s] = len as u8;
+        }
+    }
+    lengths
+}
+
+/// Canonical codes for `lengths`
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let max = *lengths.iter().max().unwrap_or(&0) as usize;
+    let mut count = vec![0u16; max + 1];
+    for &l in lengths.iter().filter(|&&l| l > 0) {
+        count[l as usize] += 1;
+    }
+    let mut next = vec![0u16; max + 2];
+    for len in 1..=max {
+        next[len + 1] = (next[len] + count[len]) << 1;
+    }
+    lengths
+        .iter()
+        .map(|&l| {
+            if l == 0 {
+                return 0;
+            }
+            let code = next[l as usize];
+            next[l as usize] += 1;
+            code
+        })
+        .collect()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    /// Write the lowest `n` bits of `value`, least significant first
+    fn write(&mut self, value: u32, n: u32) {
+        self.acc |= (value as u64) << self.bits;
+        self.bits += n;
+        while self.bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which is stored most significant bit first
+    fn write_code(&mut self, code: u16, len: u8) {
+        let reversed = code.reverse_bits() >> (16 - len as u32);
+        self.write(reversed as u32, len as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Run-length encode code lengths with the code length alphabet as (symbol, extra bits)
+fn rle_lengths(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        if len == 0 && run >= 3 {
+            let n = run.min(138);
+            if n <= 10 {
+                out.push((17, n as u8 - 3));
+            } else {
+                out.push((18, n as u8 - 11));
+            }
+            i += n;
+        } else if len != 0 && run >= 4 {
+            out.push((len, 0));
+            let n = (run - 1).min(6);
+            out.push((16, n as u8 - 3));
+            i += n + 1;
+        } else {
+            out.push((len, 0));
+            i += 1;
+        }
+    }
+    out
+}
+
+fn write_block(out: &mut BitWriter, symbols: &[Symbol], last: bool) {
+    let mut lit_freqs = vec![0u32; 286];
+    let mut dist_freqs = vec![0u32; 30];
+    for s in symbols {
+        match *s {
+            Symbol::Literal(b) => lit_freqs[b as usize] += 1,
+            Symbol::Match { length, dist } => {
+                lit_freqs[257 + code_index(&LENGTH_BASE, length)] += 1;
+                dist_freqs[code_index(&DIST_BASE, dist)] += 1;
+            }
+        }
+    }
+    lit_freqs[256] = 1;
+
+    let lit_lengths = code_lengths(&lit_freqs, 15);
+    let mut dist_lengths = code_lengths(&dist_freqs, 15);
+    if dist_lengths.iter().all(|&l| l == 0) {
+        // some decoders want at least one distance code
+        dist_lengths[0] = 1;
+    }
+    let hlit = 257.max(lit_lengths.iter().rposition(|&l| l > 0).unwrap() + 1);
+    let hdist = 1.max(dist_lengths.iter().rposition(|&l| l > 0).unwrap() + 1);
+
+    // both code lengths are sent together, coded with the code length code
+    let all: Vec<u8> = lit_lengths[..hlit]
+        .iter()
+        .chain(&dist_lengths[..hdist])
+        .copied()
+        .collect();
+    let rle = rle_lengths(&all);
+    let mut clen_freqs = vec![0u32; 19];
+    for &(s, _) in rle.iter() {
+        clen_freqs[s as usize] += 1;
+    }
+    let clen_lengths = code_lengths(&clen_freqs, 7);
+    let clen_codes = canonical_codes(&clen_lengths);
+    let hclen = 4.max(
+        CLEN_ORDER
+            .iter()
+            .rposition(|&s| clen_lengths[s] > 0)
+            .unwrap()
+            + 1,
+    );
+
+    out.write(last as u32, 1);
+    out.write(2, 2);
+    out.write(hlit as u32 - 257, 5);
+    out.write(hdist as u32 - 1, 5);
+    out.write(hclen as u32 - 4, 4);
+    for &s in CLEN_ORDER[..hclen].iter() {
+        out.write(clen_lengths[s] as u32, 3);
+    }
+    for &(s, extra) in rle.iter() {
+        out.write_code(clen_codes[s as usize], clen_lengths[s as usize]);
+        match s {
+            16 => out.write(extra as u32, 2),
+            17 => out.write(extra as u32, 3),
+            18 => out.write(extra as u32, 7),
+            _ => {}
+        }
+    }
+
+    let lit_codes = canonical_codes(&lit_lengths);
+    let dist_codes = canonical_codes(&dist_lengths);
+    for s in symbols {
+        match *s {
+            Symbol::Literal(b) => out.write_code(lit_codes[b as usize], lit_lengths[b as usize]),
+            Symbol::Match { length, dist } => {
+                let l = code_index(&LENGTH_BASE, length);
+                out.write_code(lit_codes[257 + l], lit_lengths[257 + l]);
+                out.write((length - LENGTH_BASE[l]) as u32, LENGTH_EXTRA[l] as u32);
+                let d = code_index(&DIST_BASE, dist);
+                out.write_code(dist_codes[d], dist_lengths[d]);
+                out.write((dist - DIST_BASE[d]) as u32, DIST_EXTRA[d] as u32);
+            }
+        }
+    }
+    out.write_code(lit_codes[256], lit_lengths[256]);
+}
+
+/// Compress `data` into a zlib stream
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter {
+        // 32K window, default compression
+        bytes: vec![0x78, 0x9c],
+        acc: 0,
+        bits: 0,
+    };
+    let symbols = lz77(data);
+    if symbols.is_empty() {
+        write_block(&mut out, &[], true);
+    }
+    let blocks = symbols.chunks(BLOCK_SYMBOLS).count();
+    for (i, block) in symbols.chunks(BLOCK_SYMBOLS).enumerate() {
+        write_block(&mut out, block, i + 1 == blocks);
+    }
+    let mut bytes = out.finish();
+    bytes.extend(&adler32(data).to_be_bytes());
+    bytes
+}
+
+// decompression
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.bits < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("unexpected end of data"))?;
+            self.pos += 1;
+            self.acc |= (byte as u32) << self.bits;
+            self.bits += 8;
+        }
+        let value = self.acc & ((1u64 << n) - 1) as u32;
+        self.acc >>= n;
+        self.bits -= n;
+        Ok(value)
+    }
+
+    /// Skip to the next byte boundary
+    fn align(&mut self) {
+        self.acc = 0;
+        self.bits = 0;
+    }
+}
+
+/// Canonical Huffman decoding table
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; 16],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left: i32 = 1;
+        for &count in counts.iter().skip(1) {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("too many Huffman codes"));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (s, &l) in lengths.iter().enumerate() {
+            if l > 0 {
+                symbols[offsets[l as usize] as usize] = s as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut BitReader) -> io::Result<u16> {
+        // codes of each length are consecutive, after the codes of the shorter lengths
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+/// Returns an error if `len` more bytes would make `out` longer than `max_len`
+fn check_len(out: &[u8], len: usize, max_len: usize) -> io::Result<()> {
+    if out.len().saturating_add(len) > max_len {
+        return Err(invalid(&format!("more than {} bytes of data", max_len)));
+    }
+    Ok(())
+}
+
+fn inflate_block(
+    input: &mut BitReader,
+    out: &mut Vec<u8>,
+    max_len: usize,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let s = lit.decode(input)? as usize;
+        match s {
+            0..=255 => {
+                check_len(out, 1, max_len)?;
+                out.push(s as u8)
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let l = s - 257;
+                let length = LENGTH_BASE[l] as usize + input.bits(LENGTH_EXTRA[l] as u32)? as usize;
+                let d = dist.decode(input)? as usize;
+                if d >= 30 {
+                    return Err(invalid("bad distance code"));
+                }
+                let distance = DIST_BASE[d] as usize + input.bits(DIST_EXTRA[d] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(invalid("distance too far back"));
+                }
+                check_len(out, length, max_len)?;
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+            _ => return Err(invalid("bad length code")),
+        }
+    }
+}
+
+/// Read the code lengths of a dynamic block
+fn dynamic_codes(input: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let hlit = input.bits(5)? as usize + 257;
+    let hdist = input.bits(5)? as usize + 1;
+    let hclen = input.bits(4)? as usize + 4;
+    let mut clen_lengths = [0u8; 19];
+    for &s in CLEN_ORDER[..hclen].iter() {
+        clen_lengths[s] = input.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clen_lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < hlit + hdist {
+        let (value, repeat) = match clen.decode(input)? {
+            s @ 0..=15 => (s as u8, 1),
+            16 => {
+                let last = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("repeat with no length"))?;
+                (last, 3 + input.bits(2)?)
+            }
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > hlit + hdist {
+        return Err(invalid("too many code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..hlit])?,
+        Huffman::new(&lengths[hlit..])?,
+    ))
+}
+
+/// Decompress a zlib stream
+///
+/// Stops with an error as soon as there are more than `max_len` bytes of data,
+/// so a small stream can't make a huge allocation.
+pub fn decompress(data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    if data.len() < 6 {
+        return Err(invalid("too short"));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) {
+        return Err(invalid("bad header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("preset dictionaries are not supported"));
+    }
+
+    let mut input = BitReader {
+        data: &data[2..],
+        pos: 0,
+        acc: 0,
+        bits: 0,
+    };
+    let mut out = vec![];
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let pos = input.pos;
+                let header = input
+                    .data
+                    .get(pos..pos + 4)
+                    .ok_or_else(|| invalid("unexpected end of data"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let nlen = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if len != !nlen & 0xffff {
+                    return Err(invalid("bad stored block length"));
+                }
+                let stored = input
+                    .data
+                    .get(pos + 4..pos + 4 + len)
+                    .ok_or_else(|| invalid("unexpected end of data"))?;
+                check_len(&out, len, max_len)?;
+                out.extend(stored);
+                input.pos = pos + 4 + len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (s, l) in lengths.iter_mut().enumerate() {
+                    *l = match s {
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8,
+                    };
+                }
+                let lit = Huffman::new(&lengths)?;
+                let dist = Huffman::new(&[5; 30])?;
+                inflate_block(&mut input, &mut out, max_len, &lit, &dist)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_codes(&mut input)?;
+                inflate_block(&mut input, &mut out, max_len, &lit, &dist)?;
+            }
+            _ => return Err(invalid("bad block type")),
+        }
+        if last {
+            break;
+        }
+    }
+
+    input.align();
+    let checksum = input
+        .data
+        .get(input.pos..input.pos + 4)
+        .ok_or_else(|| invalid("missing checksum"))?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = b"a rose is a rose is a rose is a rose".to_vec();
+        let noise: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let runs: Vec<u8> = (0..300_000u32).map(|i| (i / 1000) as u8).collect();
+        for data in [vec![], vec![7], text, noise, runs.clone()].iter() {
+            assert_eq!(*data, decompress(&compress(data), usize::MAX).unwrap());
+        }
+        assert!(compress(&runs).len() < runs.len() / 50);
+    }
+
+    #[test]
+    fn stored_and_fixed_blocks() {
+        // "hello" stored, then "hello" with fixed codes, as written by zlib
+        let stored = [
+            0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o', 0x06, 0x2c,
+            0x02, 0x15,
+        ];
+        assert_eq!(b"hello".to_vec(), decompress(&stored, usize::MAX).unwrap());
+        let fixed = [
+            0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x06, 0x2c, 0x02, 0x15,
+        ];
+        assert_eq!(b"hello".to_vec(), decompress(&fixed, usize::MAX).unwrap());
+    }
+
+    #[test]
+    fn bad_data() {
+        let mut data = compress(b"some data to break");
+        let n = data.len();
+        data[n - 1] ^= 1;
+        assert!(decompress(&data, usize::MAX).is_err());
+        assert!(decompress(&data[..n - 6], usize::MAX).is_err());
+        assert!(decompress(&[0x78, 0x9c, 0xff, 0xff, 0xff, 0xff], usize::MAX).is_err());
+
+        // stops at the limit instead of inflating everything
+        let zeros = compress(&[0; 100_000]);
+        assert_eq!(100_000, decompress(&zeros, 100_000).unwrap().len());
+        assert_eq!(
+            "invalid zlib data: more than 99999 bytes of data",
+            decompress(&zeros, 99_999).unwrap_err().to_string()
+        );
+        assert!(decompress(&compress(b"hello"), 4).is_err());
+    }
+
+    #[test]
+    fn lengths_are_limited() {
+        // fibonacci frequencies make the deepest possible tree
+        let mut freqs = vec![1u32, 1];
+        while freqs.len() < 30 {
+            let n = freqs.len();
+            freqs.push(freqs[n - 1] + freqs[n - 2]);
+        }
+        let lengths = code_lengths(&freqs, 15);
+        assert!(lengths.iter().all(|&l| l > 0 && l <= 15));
+        let kraft: f64 = lengths.iter().map(|&l| 0.5f64.powi(l as i32)).sum();
+        assert!(kraft <= 1.);
+    }
+}
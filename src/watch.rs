@@ -0,0 +1,116 @@
+//! Polling files for changes, to render scripts again as they're edited
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often files are checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a file looked like when it was last checked, `None` if it couldn't be read
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Files being watched, along with when they were last modified
+#[derive(Default)]
+pub struct Watcher {
+    files: Vec<(PathBuf, Stamp)>,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Watcher { files: Vec::new() }
+    }
+
+    /// Watch `path` for changes from now on, does nothing if it's already watched
+    ///
+    /// Files that don't exist yet are watched too, and count as changed once they do.
+    pub fn watch(&mut self, path: &Path) {
+        if self.files.iter().all(|(p, _)| p != path) {
+            self.files.push((path.to_path_buf(), stamp(path)));
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Returns the files that changed since the last check
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        self.files
+            .iter_mut()
+            .filter_map(|(path, last)| {
+                let now = stamp(path);
+                if now == *last {
+                    None
+                } else {
+                    *last = now;
+                    Some(path.clone())
+                }
+            })
+            .collect()
+    }
+
+    /// Block until a watched file changes, and return the files that changed
+    ///
+    /// Waits for the files to stop changing first, so that an editor
+    /// saving in several writes only counts as one change.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let mut changed = self.changed();
+            if changed.is_empty() {
+                continue;
+            }
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let more = self.changed();
+                if more.is_empty() {
+                    return changed;
+                }
+                for path in more {
+                    if !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_changes() {
+        let dir = std::env::temp_dir().join("w8_solids_watch");
+        fs::create_dir_all(&dir).unwrap();
+        let (script, mesh) = (dir.join("a.mdl"), dir.join("a.obj"));
+        fs::write(&script, "box 0 0 0 1 1 1").unwrap();
+        let _ = fs::remove_file(&mesh);
+
+        let mut watcher = Watcher::new();
+        watcher.watch(&script);
+        watcher.watch(&mesh);
+        watcher.watch(&script);
+        assert_eq!(2, watcher.files().count());
+        assert!(watcher.changed().is_empty());
+
+        fs::write(&script, "sphere 0 0 0 1").unwrap();
+        assert_eq!(vec![script.clone()], watcher.changed());
+        assert!(watcher.changed().is_empty());
+
+        // a missing file counts as changed when it shows up
+        fs::write(&mesh, "v 0 0 0").unwrap();
+        assert_eq!(vec![mesh.clone()], watcher.wait());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}